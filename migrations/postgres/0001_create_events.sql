@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS log_events (
+    signature   TEXT        NOT NULL,
+    slot        BIGINT      NOT NULL,
+    timestamp   TIMESTAMPTZ NOT NULL,
+    program_id  TEXT        NOT NULL,
+    logs        JSONB       NOT NULL,
+    PRIMARY KEY (signature, slot)
+);
+CREATE INDEX IF NOT EXISTS log_events_slot_idx ON log_events (slot);
+CREATE INDEX IF NOT EXISTS log_events_program_id_idx ON log_events (program_id, slot);
+
+CREATE TABLE IF NOT EXISTS account_events (
+    pubkey      TEXT        NOT NULL,
+    slot        BIGINT      NOT NULL,
+    timestamp   TIMESTAMPTZ NOT NULL,
+    lamports    BIGINT      NOT NULL,
+    data        TEXT        NOT NULL,
+    PRIMARY KEY (pubkey, slot)
+);
+CREATE INDEX IF NOT EXISTS account_events_slot_idx ON account_events (slot);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS commitment_events (
+    id          BIGSERIAL   PRIMARY KEY,
+    timestamp   TIMESTAMPTZ NOT NULL,
+    status      TEXT        NOT NULL,
+    commitment  TEXT        NOT NULL,
+    slot        BIGINT      NOT NULL,
+    signature   TEXT,
+    program_id  TEXT,
+    pubkey      TEXT
+);
+CREATE UNIQUE INDEX IF NOT EXISTS commitment_events_key_idx ON commitment_events
+    (COALESCE(signature, ''), COALESCE(pubkey, ''), slot, status, commitment);
+CREATE INDEX IF NOT EXISTS commitment_events_signature_idx ON commitment_events (signature);
@@ -0,0 +1,11 @@
+-- Envelope receipt time and on-chain block time; NULL for rows written before
+-- this migration, and block_time stays NULL when it could not be resolved
+ALTER TABLE log_events
+    ADD COLUMN IF NOT EXISTS received_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS block_time  TIMESTAMPTZ;
+ALTER TABLE account_events
+    ADD COLUMN IF NOT EXISTS received_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS block_time  TIMESTAMPTZ;
+ALTER TABLE commitment_events
+    ADD COLUMN IF NOT EXISTS received_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS block_time  TIMESTAMPTZ;
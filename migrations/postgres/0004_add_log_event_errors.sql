@@ -0,0 +1,7 @@
+-- Error of failed transactions: the decoded TransactionError as JSON and its
+-- variant name; NULL for successful transactions
+ALTER TABLE log_events
+    ADD COLUMN IF NOT EXISTS err      JSONB,
+    ADD COLUMN IF NOT EXISTS err_name TEXT;
+CREATE INDEX IF NOT EXISTS log_events_err_name_idx ON log_events (err_name)
+    WHERE err_name IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Fields added by a script hook, as a JSON object; NULL if there are none
+ALTER TABLE log_events ADD COLUMN IF NOT EXISTS extra JSONB;
+ALTER TABLE account_events ADD COLUMN IF NOT EXISTS extra JSONB;
@@ -0,0 +1,3 @@
+-- Output of decoder plugins keyed by program id; NULL if nothing was decoded
+ALTER TABLE log_events ADD COLUMN IF NOT EXISTS decoded JSONB;
+ALTER TABLE account_events ADD COLUMN IF NOT EXISTS decoded JSONB;
@@ -0,0 +1,22 @@
+-- Envelope sequence number, schema version, endpoint, commitment level and
+-- subscription id; NULL for rows written before this migration.
+-- commitment_events already has a commitment column for the level the event
+-- reached, so the subscription's level goes into subscription_commitment
+ALTER TABLE log_events
+    ADD COLUMN IF NOT EXISTS seq            BIGINT,
+    ADD COLUMN IF NOT EXISTS schema_version INTEGER,
+    ADD COLUMN IF NOT EXISTS endpoint       TEXT,
+    ADD COLUMN IF NOT EXISTS commitment     TEXT,
+    ADD COLUMN IF NOT EXISTS subscription   BIGINT;
+ALTER TABLE account_events
+    ADD COLUMN IF NOT EXISTS seq            BIGINT,
+    ADD COLUMN IF NOT EXISTS schema_version INTEGER,
+    ADD COLUMN IF NOT EXISTS endpoint       TEXT,
+    ADD COLUMN IF NOT EXISTS commitment     TEXT,
+    ADD COLUMN IF NOT EXISTS subscription   BIGINT;
+ALTER TABLE commitment_events
+    ADD COLUMN IF NOT EXISTS seq                     BIGINT,
+    ADD COLUMN IF NOT EXISTS schema_version          INTEGER,
+    ADD COLUMN IF NOT EXISTS endpoint                TEXT,
+    ADD COLUMN IF NOT EXISTS subscription_commitment TEXT,
+    ADD COLUMN IF NOT EXISTS subscription            BIGINT;
@@ -0,0 +1,250 @@
+//! Structured alerts delivered by notifiers
+
+use crate::event::{format_timestamp, Envelope, Event, EventType};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// How urgent an alert is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Notification-ready description of something worth telling a human about
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub severity: Severity,
+    /// One-line summary
+    pub title: String,
+    /// Optional details; empty when the title says it all
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub body: String,
+    /// RFC3339 time the alert was raised
+    pub timestamp: String,
+    /// Event that triggered the alert, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<Envelope>,
+    /// Identifies an ongoing condition, so incidents can be deduplicated and resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dedup_key: Option<String>,
+    /// Alerts sharing a group are cooled down and summarized together
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// The condition identified by `dedup_key` has cleared
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub resolved: bool,
+}
+
+impl Alert {
+    /// Create an alert raised now
+    pub fn new(severity: Severity, title: impl Into<String>) -> Self {
+        Self {
+            severity,
+            title: title.into(),
+            body: String::new(),
+            timestamp: format_timestamp(OffsetDateTime::now_utc()).unwrap_or_default(),
+            envelope: None,
+            dedup_key: None,
+            group: None,
+            resolved: false,
+        }
+    }
+
+    /// Alert for a stored event, grouped by event type; failed transactions
+    /// and dropped forks are warnings
+    pub fn from_envelope(envelope: &Envelope) -> Self {
+        let failed = matches!(&envelope.event, Event::Log(event) if event.err.is_some());
+        let severity = if failed || envelope.event_type == EventType::Dropped {
+            Severity::Warning
+        } else {
+            Severity::Info
+        };
+
+        Self {
+            envelope: Some(envelope.clone()),
+            group: Some(envelope.event_type.as_str().to_string()),
+            ..Self::new(severity, describe(&envelope.event))
+        }
+    }
+
+    /// Notable fields of the triggering event; empty without one
+    pub fn details(&self) -> Details {
+        self.envelope
+            .as_ref()
+            .map(|envelope| Details::from_event(&envelope.event))
+            .unwrap_or_default()
+    }
+}
+
+/// Fields of an alert's event worth showing in a chat message
+#[derive(Debug, Default, PartialEq)]
+pub struct Details {
+    pub signature: Option<String>,
+    pub account: Option<String>,
+    pub program: Option<String>,
+    pub slot: Option<u64>,
+    /// Decoded event name, from a plugin's `event`, `name` or `instruction` field
+    pub event_name: Option<String>,
+    /// Remaining scalar fields of the decoded output, in key order
+    pub fields: Vec<(String, String)>,
+}
+
+impl Details {
+    /// Pull the notable fields out of an event
+    pub fn from_event(event: &Event) -> Self {
+        match event {
+            Event::Log(event) => {
+                let decoded = event
+                    .decoded
+                    .get(&event.program_id)
+                    .or_else(|| event.decoded.values().next());
+                Self {
+                    signature: Some(event.signature.clone()),
+                    program: Some(event.program_id.clone()),
+                    slot: Some(event.slot),
+                    ..Self::decoded(decoded)
+                }
+            }
+            Event::Account(event) => {
+                let decoded = event.decoded.iter().next();
+                Self {
+                    account: Some(event.pubkey.clone()),
+                    program: decoded.map(|(program, _)| program.clone()),
+                    slot: Some(event.slot),
+                    ..Self::decoded(decoded.map(|(_, value)| value))
+                }
+            }
+            Event::Commitment(event) => Self {
+                signature: event.signature.clone(),
+                account: event.pubkey.clone(),
+                program: event.program_id.clone(),
+                slot: Some(event.slot),
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Event name and scalar fields of a plugin's output
+    fn decoded(value: Option<&serde_json::Value>) -> Self {
+        let Some(object) = value.and_then(|value| value.as_object()) else {
+            return Self::default();
+        };
+
+        let mut event_name = None;
+        let mut fields = Vec::new();
+        for (key, value) in object {
+            let text = match value {
+                serde_json::Value::String(text) => text.clone(),
+                serde_json::Value::Number(_) | serde_json::Value::Bool(_) => value.to_string(),
+                _ => continue,
+            };
+            if event_name.is_none() && matches!(key.as_str(), "event" | "name" | "instruction") {
+                event_name = Some(text);
+            } else {
+                fields.push((key.clone(), text));
+            }
+        }
+        Self {
+            event_name,
+            fields,
+            ..Self::default()
+        }
+    }
+}
+
+/// One-line summary of an event
+pub fn describe(event: &Event) -> String {
+    match event {
+        Event::Log(event) => match &event.err {
+            Some(err) => format!(
+                "Failed transaction {} ({}) in slot {}",
+                event.signature, err.name, event.slot
+            ),
+            None => format!("Transaction {} in slot {}", event.signature, event.slot),
+        },
+        Event::Account(event) => format!(
+            "Account {} updated in slot {} ({} lamports)",
+            event.pubkey, event.slot, event.lamports
+        ),
+        Event::Commitment(event) => format!(
+            "{} {} at {} in slot {}",
+            event.signature.as_deref().or(event.pubkey.as_deref()).unwrap_or("event"),
+            event.status.as_str(),
+            event.commitment,
+            event.slot
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{LogEvent, TransactionError};
+
+    #[test]
+    fn test_alert_from_failed_transaction() {
+        let envelope = Envelope::test(LogEvent {
+            err: Some(TransactionError::decode(serde_json::json!("BlockhashNotFound"))),
+            ..LogEvent::new(
+                "2024-01-15T10:30:45Z".to_string(),
+                "sig".to_string(),
+                1,
+                "prog".to_string(),
+                vec![],
+            )
+        });
+
+        let alert = Alert::from_envelope(&envelope);
+        assert_eq!(alert.severity, Severity::Warning);
+        assert_eq!(alert.title, "Failed transaction sig (BlockhashNotFound) in slot 1");
+
+        let json = serde_json::to_value(&alert).unwrap();
+        assert_eq!(json["severity"], "warning");
+        assert_eq!(json["envelope"]["event"]["signature"], "sig");
+        assert!(json.get("body").is_none());
+    }
+
+    #[test]
+    fn test_details_from_decoded_log() {
+        let event = Event::Log(LogEvent {
+            decoded: [(
+                "prog".to_string(),
+                serde_json::json!({"instruction": "liquidate", "amount": 42, "owner": "abc", "path": [1]}),
+            )]
+            .into(),
+            ..LogEvent::new(
+                "2024-01-15T10:30:45Z".to_string(),
+                "sig".to_string(),
+                7,
+                "prog".to_string(),
+                vec![],
+            )
+        });
+
+        let details = Details::from_event(&event);
+        assert_eq!(details.signature.as_deref(), Some("sig"));
+        assert_eq!(details.program.as_deref(), Some("prog"));
+        assert_eq!(details.slot, Some(7));
+        assert_eq!(details.event_name.as_deref(), Some("liquidate"));
+        assert_eq!(
+            details.fields,
+            vec![
+                ("amount".to_string(), "42".to_string()),
+                ("owner".to_string(), "abc".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,603 @@
+//! Alert manager between alert sources and notifiers
+//!
+//! Every alert passes through, in order:
+//!
+//! 1. silences, matched against the alert's group and dedup key;
+//! 2. deduplication: an alert with the same key as one sent within the dedup
+//!    window is dropped;
+//! 3. grouping: after an alert is sent, later alerts in its group are held
+//!    back for the group's cooldown and then summarized in one alert.
+//!
+//! Alerts with a dedup key stand for incidents and skip grouping, so an
+//! incident that resolves and fires again is always re-triggered. Resolved
+//! alerts also skip deduplication so incidents always close. Summaries carry
+//! no dedup key and are not sent to notifiers that track incidents.
+
+use crate::alert::{Alert, Severity};
+use crate::event::format_timestamp;
+use crate::metrics::MetricsRegistry;
+use anyhow::Result;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tracing::info;
+
+/// Titles of suppressed alerts listed in a summary
+const SUMMARY_SAMPLES: usize = 5;
+
+/// How alerts are deduplicated and grouped
+#[derive(Debug, Clone, Copy)]
+pub struct AlertPolicy {
+    /// How long an alert's key suppresses identical alerts
+    pub dedup_window: Duration,
+    /// Cooldown of groups whose alerts don't set their own
+    pub cooldown: Duration,
+}
+
+/// An alert on its way to notifiers
+#[derive(Debug)]
+pub struct Routed {
+    pub alert: Alert,
+    /// Notifier names; every notifier when empty
+    pub notifiers: Vec<String>,
+    /// Overrides the policy's cooldown for the alert's group
+    pub cooldown: Option<Duration>,
+    /// Summarizes a group's held back alerts
+    pub summary: bool,
+}
+
+impl Routed {
+    pub fn new(alert: Alert, notifiers: Vec<String>) -> Self {
+        Self {
+            alert,
+            notifiers,
+            cooldown: None,
+            summary: false,
+        }
+    }
+}
+
+/// A group cooling down after one of its alerts was sent
+#[derive(Debug)]
+struct Group {
+    started: Instant,
+    until: Instant,
+    notifiers: Vec<String>,
+    suppressed: usize,
+    severity: Severity,
+    samples: Vec<String>,
+}
+
+/// Decides which alerts reach the notifiers
+///
+/// Outcomes are counted in `sol_alerts_total{result}`.
+pub struct AlertManager {
+    policy: AlertPolicy,
+    silences: Arc<Silences>,
+    /// When each recently sent dedup key stops suppressing duplicates
+    seen: HashMap<String, Instant>,
+    groups: HashMap<String, Group>,
+    metrics: MetricsRegistry,
+}
+
+impl AlertManager {
+    pub fn new(policy: AlertPolicy, silences: Arc<Silences>, metrics: MetricsRegistry) -> Self {
+        Self {
+            policy,
+            silences,
+            seen: HashMap::new(),
+            groups: HashMap::new(),
+            metrics,
+        }
+    }
+
+    /// Alerts to send now for an alert raised at `now`: the alert itself
+    /// unless suppressed, preceded by the summary of its group's last window
+    pub fn admit(&mut self, routed: Routed, now: Instant) -> Vec<Routed> {
+        let alert = &routed.alert;
+        let key = alert.dedup_key.clone().unwrap_or_else(|| alert.title.clone());
+        let group = alert.group.clone().unwrap_or_else(|| key.clone());
+
+        if self.silences.matches(&[&group, &key], now) {
+            self.record("silenced");
+            return Vec::new();
+        }
+
+        if alert.resolved {
+            self.seen.remove(&key);
+            let mut ready: Vec<Routed> = self
+                .groups
+                .remove(&group)
+                .and_then(|resolved| self.summarize(&group, resolved))
+                .into_iter()
+                .collect();
+            self.record("sent");
+            ready.push(routed);
+            return ready;
+        }
+
+        if self.seen.get(&key).is_some_and(|until| now < *until) {
+            self.record("duplicate");
+            return Vec::new();
+        }
+        self.seen.insert(key, now + self.policy.dedup_window);
+
+        if alert.dedup_key.is_some() {
+            self.record("sent");
+            return vec![routed];
+        }
+
+        let mut ready = Vec::new();
+        if let Some(current) = self.groups.get_mut(&group) {
+            if now < current.until {
+                current.suppressed += 1;
+                current.severity = current.severity.max(alert.severity);
+                if current.samples.len() < SUMMARY_SAMPLES {
+                    current.samples.push(alert.title.clone());
+                }
+                self.record("grouped");
+                return Vec::new();
+            }
+            let expired = self.groups.remove(&group);
+            ready.extend(expired.and_then(|expired| self.summarize(&group, expired)));
+        }
+
+        let cooldown = routed.cooldown.unwrap_or(self.policy.cooldown);
+        if !cooldown.is_zero() {
+            self.groups.insert(
+                group,
+                Group {
+                    started: now,
+                    until: now + cooldown,
+                    notifiers: routed.notifiers.clone(),
+                    suppressed: 0,
+                    severity: alert.severity,
+                    samples: Vec::new(),
+                },
+            );
+        }
+        self.record("sent");
+        ready.push(routed);
+        ready
+    }
+
+    /// Summaries of groups whose cooldown ended by `now`
+    pub fn due(&mut self, now: Instant) -> Vec<Routed> {
+        self.seen.retain(|_, until| now < *until);
+        let expired: Vec<String> = self
+            .groups
+            .iter()
+            .filter(|(_, group)| now >= group.until)
+            .map(|(name, _)| name.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|name| {
+                let group = self.groups.remove(&name)?;
+                self.summarize(&name, group)
+            })
+            .collect()
+    }
+
+    /// Summaries of every group still cooling down, for shutdown
+    pub fn drain(&mut self) -> Vec<Routed> {
+        let groups: Vec<(String, Group)> = self.groups.drain().collect();
+        groups
+            .into_iter()
+            .filter_map(|(name, group)| self.summarize(&name, group))
+            .collect()
+    }
+
+    /// One alert standing for a group's suppressed alerts, if there were any
+    fn summarize(&self, name: &str, group: Group) -> Option<Routed> {
+        if group.suppressed == 0 {
+            return None;
+        }
+        self.record("summary");
+
+        let mut body: Vec<String> = group.samples.iter().map(|title| format!("- {}", title)).collect();
+        if group.suppressed > group.samples.len() {
+            body.push(format!("- … and {} more", group.suppressed - group.samples.len()));
+        }
+        let window = group.until.duration_since(group.started).as_secs();
+        let title = format!(
+            "{}: {} more alert{} in the last {}s",
+            name,
+            group.suppressed,
+            if group.suppressed == 1 { "" } else { "s" },
+            window
+        );
+        Some(Routed {
+            summary: true,
+            ..Routed::new(
+                Alert {
+                    body: body.join("\n"),
+                    group: Some(name.to_string()),
+                    ..Alert::new(group.severity, title)
+                },
+                group.notifiers,
+            )
+        })
+    }
+
+    fn record(&self, result: &str) {
+        self.metrics.alerts_total.with_label_values(&[result]).inc();
+    }
+}
+
+/// Mutes alerts whose group or dedup key matches `matcher` until `ends_at`
+#[derive(Debug, Clone, Serialize)]
+pub struct Silence {
+    pub id: u64,
+    /// Exact group or dedup key, or a prefix ending in `*`
+    pub matcher: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub comment: String,
+    /// RFC3339 time the silence expires
+    pub ends_at: String,
+    #[serde(skip)]
+    expires: Instant,
+}
+
+impl Silence {
+    fn matches(&self, value: &str) -> bool {
+        match self.matcher.strip_suffix('*') {
+            Some(prefix) => value.starts_with(prefix),
+            None => value == self.matcher,
+        }
+    }
+}
+
+/// Active silences, shared between the alert manager and the silence API
+#[derive(Debug, Default)]
+pub struct Silences {
+    /// Silences and the next id to hand out
+    state: Mutex<(Vec<Silence>, u64)>,
+}
+
+impl Silences {
+    /// Silence `matcher` for `duration` from `now`
+    pub fn add(
+        &self,
+        matcher: &str,
+        duration: Duration,
+        comment: String,
+        now: Instant,
+    ) -> Result<Silence> {
+        anyhow::ensure!(!matcher.is_empty(), "matcher must not be empty");
+        anyhow::ensure!(!duration.is_zero(), "duration_secs must be positive");
+        let ends_at = OffsetDateTime::now_utc()
+            .checked_add(duration.try_into()?)
+            .and_then(|at| format_timestamp(at).ok())
+            .ok_or_else(|| anyhow::anyhow!("duration_secs is too large"))?;
+        let expires = now
+            .checked_add(duration)
+            .ok_or_else(|| anyhow::anyhow!("duration_secs is too large"))?;
+
+        let mut state = self.state.lock().unwrap();
+        state.1 += 1;
+        let silence = Silence {
+            id: state.1,
+            matcher: matcher.to_string(),
+            comment,
+            ends_at,
+            expires,
+        };
+        state.0.push(silence.clone());
+        Ok(silence)
+    }
+
+    /// Remove a silence, returning whether it existed
+    pub fn remove(&self, id: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let before = state.0.len();
+        state.0.retain(|silence| silence.id != id);
+        state.0.len() < before
+    }
+
+    /// Silences still in effect at `now`
+    pub fn active(&self, now: Instant) -> Vec<Silence> {
+        let mut state = self.state.lock().unwrap();
+        state.0.retain(|silence| now < silence.expires);
+        state.0.clone()
+    }
+
+    /// Whether any active silence matches one of `values`
+    pub fn matches(&self, values: &[&str], now: Instant) -> bool {
+        let state = self.state.lock().unwrap();
+        state.0.iter().any(|silence| {
+            now < silence.expires && values.iter().any(|value| silence.matches(value))
+        })
+    }
+}
+
+/// Body of `POST /silences`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewSilence {
+    matcher: String,
+    duration_secs: u64,
+    #[serde(default)]
+    comment: String,
+}
+
+/// `GET /silences`, `POST /silences` and `DELETE /silences/:id`
+pub fn routes(silences: Arc<Silences>) -> Router {
+    Router::new()
+        .route("/silences", get(list_silences).post(create_silence))
+        .route("/silences/:id", delete(delete_silence))
+        .with_state(silences)
+}
+
+async fn list_silences(State(silences): State<Arc<Silences>>) -> Json<Vec<Silence>> {
+    Json(silences.active(Instant::now()))
+}
+
+async fn create_silence(
+    State(silences): State<Arc<Silences>>,
+    Json(request): Json<NewSilence>,
+) -> Response {
+    let duration = Duration::from_secs(request.duration_secs);
+    match silences.add(&request.matcher, duration, request.comment, Instant::now()) {
+        Ok(silence) => {
+            info!("Silenced {} until {}", silence.matcher, silence.ends_at);
+            (StatusCode::CREATED, Json(silence)).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+async fn delete_silence(State(silences): State<Arc<Silences>>, Path(id): Path<u64>) -> StatusCode {
+    if silences.remove(id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(cooldown_secs: u64) -> (AlertManager, Arc<Silences>, MetricsRegistry) {
+        let metrics = MetricsRegistry::default();
+        let silences = Arc::new(Silences::default());
+        let policy = AlertPolicy {
+            dedup_window: Duration::from_secs(300),
+            cooldown: Duration::from_secs(cooldown_secs),
+        };
+        (AlertManager::new(policy, silences.clone(), metrics.clone()), silences, metrics)
+    }
+
+    fn alert(group: &str, title: &str) -> Routed {
+        Routed::new(
+            Alert {
+                group: Some(group.to_string()),
+                ..Alert::new(Severity::Info, title)
+            },
+            vec!["slack".to_string()],
+        )
+    }
+
+    fn titles(routed: &[Routed]) -> Vec<&str> {
+        routed.iter().map(|routed| routed.alert.title.as_str()).collect()
+    }
+
+    fn count(metrics: &MetricsRegistry, result: &str) -> f64 {
+        metrics.alerts_total.with_label_values(&[result]).get()
+    }
+
+    #[test]
+    fn test_burst_is_summarized() {
+        let (mut manager, _, metrics) = manager(60);
+        let start = Instant::now();
+
+        let mut sent = Vec::new();
+        for i in 0..500 {
+            sent.extend(manager.admit(alert("burst", &format!("event {}", i)), start));
+        }
+        assert_eq!(titles(&sent), vec!["event 0"]);
+        assert!(manager.due(start + Duration::from_secs(59)).is_empty());
+
+        let summaries = manager.due(start + Duration::from_secs(60));
+        assert_eq!(titles(&summaries), vec!["burst: 499 more alerts in the last 60s"]);
+        assert_eq!(summaries[0].notifiers, vec!["slack"]);
+        assert!(summaries[0].summary);
+        assert!(summaries[0].alert.dedup_key.is_none());
+        assert_eq!(summaries[0].alert.body.lines().last(), Some("- … and 494 more"));
+        assert_eq!(count(&metrics, "sent"), 1.0);
+        assert_eq!(count(&metrics, "grouped"), 499.0);
+        assert_eq!(count(&metrics, "summary"), 1.0);
+
+        // The next window starts fresh
+        let next = manager.admit(alert("burst", "event 500"), start + Duration::from_secs(61));
+        assert_eq!(titles(&next), vec!["event 500"]);
+    }
+
+    #[test]
+    fn test_expired_group_is_summarized_before_next_alert() {
+        let (mut manager, _, _) = manager(10);
+        let start = Instant::now();
+
+        manager.admit(alert("rule", "first"), start);
+        manager.admit(alert("rule", "second"), start + Duration::from_secs(1));
+        let sent = manager.admit(alert("rule", "third"), start + Duration::from_secs(11));
+        assert_eq!(titles(&sent), vec!["rule: 1 more alert in the last 10s", "third"]);
+        assert!(manager.drain().is_empty());
+    }
+
+    #[test]
+    fn test_per_rule_cooldown() {
+        let (mut manager, _, _) = manager(60);
+        let start = Instant::now();
+        let quiet = |title: &str, secs| Routed {
+            cooldown: Some(Duration::from_secs(secs)),
+            ..alert("quiet", title)
+        };
+
+        // No cooldown: every alert goes out
+        assert_eq!(manager.admit(quiet("a", 0), start).len(), 1);
+        assert_eq!(manager.admit(quiet("b", 0), start).len(), 1);
+
+        assert_eq!(manager.admit(quiet("c", 5), start).len(), 1);
+        assert!(manager.admit(quiet("d", 5), start + Duration::from_secs(4)).is_empty());
+        let summaries = manager.drain();
+        assert_eq!(titles(&summaries), vec!["quiet: 1 more alert in the last 5s"]);
+    }
+
+    #[test]
+    fn test_duplicates_and_resolved_alerts() {
+        let (mut manager, _, metrics) = manager(0);
+        let start = Instant::now();
+        let incident = |resolved| {
+            Routed::new(
+                Alert {
+                    dedup_key: Some("listener/disconnected".to_string()),
+                    resolved,
+                    ..Alert::new(Severity::Critical, "Disconnected")
+                },
+                vec![],
+            )
+        };
+
+        assert_eq!(manager.admit(incident(false), start).len(), 1);
+        assert!(manager.admit(incident(false), start + Duration::from_secs(10)).is_empty());
+        assert_eq!(count(&metrics, "duplicate"), 1.0);
+
+        // Resolving clears the key, so the next incident goes out
+        assert_eq!(manager.admit(incident(true), start + Duration::from_secs(20)).len(), 1);
+        assert_eq!(manager.admit(incident(false), start + Duration::from_secs(30)).len(), 1);
+
+        // Keys expire after the dedup window
+        assert_eq!(manager.admit(alert("other", "same"), start).len(), 1);
+        assert!(manager.admit(alert("other", "same"), start).is_empty());
+        manager.due(start + Duration::from_secs(300));
+        assert_eq!(manager.admit(alert("other", "same"), start + Duration::from_secs(300)).len(), 1);
+    }
+
+    #[test]
+    fn test_incident_retriggers_within_cooldown() {
+        let (mut manager, _, metrics) = manager(60);
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        let incident = |resolved| {
+            Routed::new(
+                Alert {
+                    dedup_key: Some("listener/error_rate".to_string()),
+                    resolved,
+                    ..Alert::new(Severity::Critical, "Error rate")
+                },
+                vec![],
+            )
+        };
+
+        // Trigger, resolve and trigger again, all within one cooldown
+        assert_eq!(manager.admit(incident(false), start).len(), 1);
+        assert_eq!(manager.admit(incident(true), start + second).len(), 1);
+        let retriggered = manager.admit(incident(false), start + second * 2);
+        assert_eq!(retriggered.len(), 1);
+        assert!(!retriggered[0].alert.resolved);
+        assert_eq!(count(&metrics, "sent"), 3.0);
+        assert_eq!(count(&metrics, "grouped"), 0.0);
+        assert!(manager.drain().is_empty());
+    }
+
+    #[test]
+    fn test_resolving_ends_group() {
+        let (mut manager, _, _) = manager(60);
+        let start = Instant::now();
+        let keyed = |title: &str, resolved| {
+            Routed::new(
+                Alert {
+                    group: Some("treasury".to_string()),
+                    dedup_key: Some("treasury/drain".to_string()),
+                    resolved,
+                    ..Alert::new(Severity::Warning, title)
+                },
+                vec![],
+            )
+        };
+
+        manager.admit(alert("treasury", "first"), start);
+        manager.admit(alert("treasury", "second"), start);
+        // Keyed alerts skip the group's cooldown
+        assert_eq!(titles(&manager.admit(keyed("drain", false), start)), vec!["drain"]);
+        // Resolving ends the group, summarizing what it held back
+        let sent = manager.admit(keyed("drained", true), start);
+        assert_eq!(titles(&sent), vec!["treasury: 1 more alert in the last 60s", "drained"]);
+        assert_eq!(titles(&manager.admit(alert("treasury", "third"), start)), vec!["third"]);
+    }
+
+    #[test]
+    fn test_silences() {
+        let (mut manager, silences, metrics) = manager(0);
+        let start = Instant::now();
+        let hour = Duration::from_secs(3600);
+
+        let silence = silences.add("liquid*", hour, "maintenance".to_string(), start).unwrap();
+        silences.add("other", hour, String::new(), start).unwrap();
+        assert!(manager.admit(alert("liquidation", "a"), start).is_empty());
+        assert_eq!(manager.admit(alert("burst", "b"), start).len(), 1);
+        assert_eq!(count(&metrics, "silenced"), 1.0);
+
+        // Expired silences no longer apply and are pruned
+        assert_eq!(manager.admit(alert("liquidation", "c"), start + hour).len(), 1);
+        assert!(silences.active(start + hour).is_empty());
+
+        let silence2 = silences.add("liquidation", hour, String::new(), start).unwrap();
+        assert!(silence2.id > silence.id);
+        assert!(silences.remove(silence2.id));
+        assert!(!silences.remove(silence2.id));
+        assert_eq!(manager.admit(alert("liquidation", "d"), start).len(), 1);
+
+        assert!(silences.add("", hour, String::new(), start).is_err());
+        assert!(silences.add("x", Duration::ZERO, String::new(), start).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_silence_api() {
+        let silences = Arc::new(Silences::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/silences", listener.local_addr().unwrap());
+        let app = routes(silences.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let client = reqwest::Client::new();
+
+        let created = client
+            .post(&url)
+            .json(&serde_json::json!({"matcher": "burst", "duration_secs": 600, "comment": "deploy"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(created.status(), reqwest::StatusCode::CREATED);
+        let created: serde_json::Value = created.json().await.unwrap();
+        assert_eq!(created["matcher"], "burst");
+        assert_eq!(created["comment"], "deploy");
+        assert!(silences.matches(&["burst"], Instant::now()));
+
+        let invalid = client
+            .post(&url)
+            .json(&serde_json::json!({"matcher": "burst", "duration_secs": 0}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let listed: serde_json::Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+
+        let id = created["id"].as_u64().unwrap();
+        let deleted = client.delete(format!("{}/{}", url, id)).send().await.unwrap();
+        assert_eq!(deleted.status(), reqwest::StatusCode::NO_CONTENT);
+        let missing = client.delete(format!("{}/{}", url, id)).send().await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+        assert!(!silences.matches(&["burst"], Instant::now()));
+    }
+}
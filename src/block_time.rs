@@ -0,0 +1,188 @@
+//! On-chain block time resolution
+//!
+//! Notifications only carry a slot. The block's `blockTime` is looked up with
+//! `getBlockTime` over HTTP JSON-RPC and cached per slot, since many events
+//! share a slot.
+//!
+//! Lookups run inline in the subscription's read loop, so they are kept
+//! short and failures are remembered: once a slot's block time can't be
+//! fetched (as is usual at `processed` commitment), that slot and every later
+//! one are skipped for a moment instead of each paying a failing round-trip.
+
+use crate::http_rpc::HttpRpcClient;
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tracing::debug;
+
+/// Slots kept in the cache; the oldest slots are evicted first
+const CACHE_CAPACITY: usize = 4096;
+
+/// Longest a single lookup may hold up the read loop
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long a failed lookup skips its slot and every later slot
+const UNAVAILABLE_TTL: Duration = Duration::from_secs(2);
+
+/// Per-slot cache of block times fetched over HTTP
+pub struct BlockTimeCache {
+    rpc: HttpRpcClient,
+    /// Slot -> unix timestamp
+    cache: Mutex<BTreeMap<u64, i64>>,
+    /// Earliest slot of the last failed lookup, and until when slots from it
+    /// on are not looked up
+    unavailable: Mutex<Option<(u64, Instant)>>,
+    unavailable_ttl: Duration,
+}
+
+impl BlockTimeCache {
+    /// Create a cache resolving against the HTTP RPC endpoint `url`
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self {
+            rpc: HttpRpcClient::new(url)?,
+            cache: Mutex::new(BTreeMap::new()),
+            unavailable: Mutex::new(None),
+            unavailable_ttl: UNAVAILABLE_TTL,
+        })
+    }
+
+    /// Block time of `slot`, or `None` if the node can't provide it yet
+    ///
+    /// After a failed lookup, that slot and later ones return `None` without
+    /// a request for a short while; they are retried once it has passed.
+    pub async fn block_time(&self, slot: u64) -> Option<OffsetDateTime> {
+        let cached = self.cache.lock().ok()?.get(&slot).copied();
+        let timestamp = match cached {
+            Some(timestamp) => timestamp,
+            None => {
+                let now = Instant::now();
+                let skipped = self
+                    .unavailable
+                    .lock()
+                    .ok()?
+                    .is_some_and(|(from, until)| slot >= from && now < until);
+                if skipped {
+                    return None;
+                }
+
+                let fetched = tokio::time::timeout(LOOKUP_TIMEOUT, self.fetch(slot))
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Timed out after {:?}", LOOKUP_TIMEOUT)));
+                match fetched {
+                    Ok(timestamp) => {
+                        self.insert(slot, timestamp);
+                        timestamp
+                    }
+                    Err(e) => {
+                        debug!("No block time for slot {}: {:#}", slot, e);
+                        if let Ok(mut unavailable) = self.unavailable.lock() {
+                            *unavailable = Some((slot, now + self.unavailable_ttl));
+                        }
+                        return None;
+                    }
+                }
+            }
+        };
+
+        OffsetDateTime::from_unix_timestamp(timestamp).ok()
+    }
+
+    /// Call `getBlockTime` for a slot
+    async fn fetch(&self, slot: u64) -> Result<i64> {
+        self.rpc
+            .call::<Option<i64>>("getBlockTime", json!([slot]))
+            .await?
+            .context("Block time not available")
+    }
+
+    fn insert(&self, slot: u64, timestamp: i64) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(slot, timestamp);
+            while cache.len() > CACHE_CAPACITY {
+                cache.pop_first();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Serve `getBlockTime` locally, answering `slot * 10` and counting requests
+    async fn spawn_rpc(requests: Arc<AtomicUsize>) -> String {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| {
+                let requests = requests.clone();
+                async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    let slot = request["params"][0].as_i64().unwrap();
+                    if slot == 0 {
+                        return Json(json!({
+                            "jsonrpc": "2.0",
+                            "id": 1,
+                            "error": {"code": -32004, "message": "Block not available for slot 0"}
+                        }));
+                    }
+                    Json(json!({"jsonrpc": "2.0", "id": 1, "result": slot * 10}))
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_block_time_is_cached_per_slot() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let cache = BlockTimeCache::new(&spawn_rpc(requests.clone()).await).unwrap();
+
+        let time = cache.block_time(170_531_460).await.unwrap();
+        assert_eq!(time.unix_timestamp(), 1_705_314_600);
+        cache.block_time(170_531_460).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_block_skips_later_slots() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = spawn_rpc(requests.clone()).await;
+        let cache = BlockTimeCache::new(&url).unwrap();
+
+        assert!(cache.block_time(0).await.is_none());
+        assert!(cache.block_time(0).await.is_none());
+        // Later slots are skipped too, without a request
+        assert!(cache.block_time(7).await.is_none());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Once the failure has expired, lookups are retried
+        let cache = BlockTimeCache {
+            unavailable_ttl: Duration::ZERO,
+            ..BlockTimeCache::new(&url).unwrap()
+        };
+        assert!(cache.block_time(0).await.is_none());
+        assert!(cache.block_time(7).await.is_some());
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_errors_leave_out_the_url() {
+        let url = spawn_rpc(Arc::new(AtomicUsize::new(0))).await;
+        // Only `/` is served, so this path fails with 404
+        let cache = BlockTimeCache::new(&format!("{}/secret-key", url)).unwrap();
+
+        let err = cache.fetch(7).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("404"), "{:#}", err);
+        assert!(!format!("{:#}", err).contains("secret-key"), "{:#}", err);
+    }
+}
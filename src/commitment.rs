@@ -0,0 +1,600 @@
+//! Commitment promotion tracking
+//!
+//! Subscribing at `processed` gives the lowest latency, but those events can
+//! still be rolled back. The tracker remembers every event below `finalized`
+//! and polls the HTTP RPC endpoint: transactions through
+//! `getSignatureStatuses`, account updates by checking with `getBlocks`
+//! whether their slot made it into the confirmed and finalized chain. Every
+//! level reached produces a `promoted` event; events whose fork was abandoned
+//! produce a `dropped` event.
+
+use crate::config::Commitment;
+use crate::event::{
+    format_timestamp, redact_endpoint, CommitmentEvent, CommitmentStatus, Envelope, Event,
+};
+use crate::http_rpc::HttpRpcClient;
+use crate::pipeline::Pipeline;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info, warn};
+
+/// Events tracked at once; further events are not tracked
+const MAX_PENDING: usize = 100_000;
+
+/// Signatures per `getSignatureStatuses` request (RPC limit)
+const SIGNATURE_BATCH: usize = 256;
+
+/// Widest slot range a `getBlocks` request may cover (RPC limit)
+const MAX_BLOCK_RANGE: u64 = 500_000;
+
+/// A transaction seen below `finalized`
+struct PendingSignature {
+    slot: u64,
+    program_id: String,
+    level: Commitment,
+    subscription: u64,
+}
+
+/// An account update seen below `finalized`
+struct PendingAccount {
+    level: Commitment,
+    subscription: u64,
+}
+
+#[derive(Default)]
+struct Pending {
+    signatures: HashMap<String, PendingSignature>,
+    /// Keyed by `(slot, pubkey)`
+    accounts: BTreeMap<(u64, String), PendingAccount>,
+}
+
+impl Pending {
+    fn len(&self) -> usize {
+        self.signatures.len() + self.accounts.len()
+    }
+}
+
+/// What a poll found out about one tracked event
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Unchanged,
+    /// Reached each of `levels`, in order; `slot` is where it landed
+    Promoted { levels: Vec<Commitment>, slot: u64 },
+    /// Missing from the chain at `level`
+    Dropped { level: Commitment },
+}
+
+/// A commitment event and the subscription of the event it follows up on
+struct Transition {
+    event: CommitmentEvent,
+    subscription: u64,
+}
+
+/// `{"context": ..., "value": ...}` RPC result
+#[derive(Debug, Deserialize)]
+struct WithContext<T> {
+    value: T,
+}
+
+/// Entry of a `getSignatureStatuses` result
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignatureStatus {
+    slot: u64,
+    #[serde(default)]
+    confirmation_status: Option<String>,
+}
+
+/// Follows events from a sub-`finalized` subscription to their final state
+pub struct CommitmentTracker {
+    rpc: HttpRpcClient,
+    /// Commitment level of the subscription
+    level: Commitment,
+    poll_interval: Duration,
+    pending: Mutex<Pending>,
+}
+
+impl CommitmentTracker {
+    /// Create a tracker polling the HTTP RPC endpoint `url`
+    pub fn new(url: &str, level: Commitment, poll_interval: Duration) -> Result<Self> {
+        info!(
+            "Tracking commitment of {} events via {} every {:?}",
+            level.as_str(),
+            redact_endpoint(url),
+            poll_interval
+        );
+        Ok(Self {
+            rpc: HttpRpcClient::new(url)?,
+            level,
+            poll_interval,
+            pending: Mutex::new(Pending::default()),
+        })
+    }
+
+    /// Start tracking a stored log or account event
+    pub fn track(&self, envelope: &Envelope) {
+        let Ok(mut pending) = self.pending.lock() else {
+            return;
+        };
+        if pending.len() >= MAX_PENDING {
+            warn!(
+                "Commitment tracker full ({} events), not tracking slot {}",
+                MAX_PENDING,
+                envelope.event.slot()
+            );
+            return;
+        }
+
+        match &envelope.event {
+            Event::Log(event) => {
+                pending
+                    .signatures
+                    .entry(event.signature.clone())
+                    .or_insert_with(|| PendingSignature {
+                        slot: event.slot,
+                        program_id: event.program_id.clone(),
+                        level: self.level,
+                        subscription: envelope.subscription,
+                    });
+            }
+            Event::Account(event) => {
+                pending
+                    .accounts
+                    .entry((event.slot, event.pubkey.clone()))
+                    .or_insert(PendingAccount {
+                        level: self.level,
+                        subscription: envelope.subscription,
+                    });
+            }
+            Event::Commitment(_) => {}
+        }
+    }
+
+    /// Poll forever, storing promoted and dropped events through `pipeline`
+    pub async fn run(&self, pipeline: &Pipeline<'_>) {
+        let metrics = pipeline.metrics;
+        let mut ticker = tokio::time::interval(self.poll_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            let transitions = match self.poll().await {
+                Ok(transitions) => transitions,
+                Err(e) => {
+                    warn!("Commitment poll failed: {:#}", e);
+                    continue;
+                }
+            };
+
+            for transition in transitions {
+                metrics
+                    .commitment_events_total
+                    .with_label_values(&[transition.event.status.as_str()])
+                    .inc();
+
+                let envelope = pipeline.source.wrap(
+                    transition.event,
+                    transition.subscription,
+                    OffsetDateTime::now_utc(),
+                    None,
+                );
+                let result = match envelope {
+                    Ok(envelope) => pipeline.store(&envelope).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    error!("Failed to write commitment event: {:#}", e);
+                    metrics.errors_total.inc();
+                }
+            }
+        }
+    }
+
+    /// Check every tracked event once
+    async fn poll(&self) -> Result<Vec<Transition>> {
+        let (has_signatures, has_accounts) = match self.pending.lock() {
+            Ok(pending) => (!pending.signatures.is_empty(), !pending.accounts.is_empty()),
+            Err(_) => anyhow::bail!("Commitment tracker lock poisoned"),
+        };
+        if !has_signatures && !has_accounts {
+            return Ok(Vec::new());
+        }
+
+        let finalized_slot = self.slot(Commitment::Finalized).await?;
+        let mut transitions = Vec::new();
+        if has_signatures {
+            transitions.extend(self.poll_signatures(finalized_slot).await?);
+        }
+        if has_accounts {
+            let confirmed_slot = self.slot(Commitment::Confirmed).await?;
+            transitions.extend(self.poll_accounts(confirmed_slot, finalized_slot).await?);
+        }
+        Ok(transitions)
+    }
+
+    async fn slot(&self, level: Commitment) -> Result<u64> {
+        self.rpc
+            .call("getSlot", json!([{ "commitment": level.as_str() }]))
+            .await
+    }
+
+    async fn blocks(&self, start: u64, end: u64, level: Commitment) -> Result<HashSet<u64>> {
+        if start > end {
+            return Ok(HashSet::new());
+        }
+        let blocks: Vec<u64> = self
+            .rpc
+            .call(
+                "getBlocks",
+                json!([start, end, { "commitment": level.as_str() }]),
+            )
+            .await?;
+        Ok(blocks.into_iter().collect())
+    }
+
+    async fn poll_signatures(&self, finalized_slot: u64) -> Result<Vec<Transition>> {
+        let signatures: Vec<String> = match self.pending.lock() {
+            Ok(pending) => pending.signatures.keys().cloned().collect(),
+            Err(_) => anyhow::bail!("Commitment tracker lock poisoned"),
+        };
+
+        let mut statuses = HashMap::new();
+        for chunk in signatures.chunks(SIGNATURE_BATCH) {
+            let result: WithContext<Vec<Option<SignatureStatus>>> = self
+                .rpc
+                .call(
+                    "getSignatureStatuses",
+                    json!([chunk, { "searchTransactionHistory": false }]),
+                )
+                .await?;
+            statuses.extend(chunk.iter().cloned().zip(result.value));
+        }
+
+        let now = format_timestamp(OffsetDateTime::now_utc())?;
+        let Ok(mut pending) = self.pending.lock() else {
+            anyhow::bail!("Commitment tracker lock poisoned");
+        };
+
+        let mut transitions = Vec::new();
+        for (signature, status) in statuses {
+            let Some(tracked) = pending.signatures.get_mut(&signature) else {
+                continue;
+            };
+            let outcome = signature_outcome(tracked, status.as_ref(), finalized_slot);
+            let finished = apply(&outcome, &mut tracked.level);
+
+            let event = |status, level: Commitment, slot| CommitmentEvent {
+                timestamp: now.clone(),
+                status,
+                commitment: level.as_str().to_string(),
+                slot,
+                signature: Some(signature.clone()),
+                program_id: Some(tracked.program_id.clone()),
+                pubkey: None,
+            };
+            let subscription = tracked.subscription;
+            transitions.extend(
+                outcome_events(&outcome, tracked.slot, event)
+                    .into_iter()
+                    .map(|event| Transition { event, subscription }),
+            );
+
+            if finished {
+                pending.signatures.remove(&signature);
+            }
+        }
+
+        Ok(transitions)
+    }
+
+    async fn poll_accounts(&self, confirmed_slot: u64, finalized_slot: u64) -> Result<Vec<Transition>> {
+        let oldest = match self.pending.lock() {
+            Ok(pending) => pending.accounts.keys().next().map(|(slot, _)| *slot),
+            Err(_) => anyhow::bail!("Commitment tracker lock poisoned"),
+        };
+        let Some(oldest) = oldest else {
+            return Ok(Vec::new());
+        };
+
+        let start = |end: u64| oldest.max(end.saturating_sub(MAX_BLOCK_RANGE - 1));
+        let confirmed_start = start(confirmed_slot);
+        let finalized_start = start(finalized_slot);
+        let confirmed = self
+            .blocks(confirmed_start, confirmed_slot, Commitment::Confirmed)
+            .await?;
+        let finalized = self
+            .blocks(finalized_start, finalized_slot, Commitment::Finalized)
+            .await?;
+
+        let now = format_timestamp(OffsetDateTime::now_utc())?;
+        let Ok(mut pending) = self.pending.lock() else {
+            anyhow::bail!("Commitment tracker lock poisoned");
+        };
+
+        let mut transitions = Vec::new();
+        let mut finished = Vec::new();
+        for ((slot, pubkey), tracked) in pending.accounts.iter_mut() {
+            if *slot < finalized_start.min(confirmed_start) {
+                debug!("Slot {} is too old to check, no longer tracking {}", slot, pubkey);
+                finished.push((*slot, pubkey.clone()));
+                continue;
+            }
+
+            let outcome = account_outcome(
+                *slot,
+                tracked.level,
+                (confirmed_slot, &confirmed),
+                (finalized_slot, &finalized),
+            );
+            if apply(&outcome, &mut tracked.level) {
+                finished.push((*slot, pubkey.clone()));
+            }
+
+            let event = |status, level: Commitment, slot| CommitmentEvent {
+                timestamp: now.clone(),
+                status,
+                commitment: level.as_str().to_string(),
+                slot,
+                signature: None,
+                program_id: None,
+                pubkey: Some(pubkey.clone()),
+            };
+            let subscription = tracked.subscription;
+            transitions.extend(
+                outcome_events(&outcome, *slot, event)
+                    .into_iter()
+                    .map(|event| Transition { event, subscription }),
+            );
+        }
+
+        for key in finished {
+            pending.accounts.remove(&key);
+        }
+
+        Ok(transitions)
+    }
+}
+
+/// Decide what happened to a transaction from its signature status
+///
+/// A signature unknown to the node after the finalized root passed its slot
+/// was on an abandoned fork.
+fn signature_outcome(
+    tracked: &PendingSignature,
+    status: Option<&SignatureStatus>,
+    finalized_slot: u64,
+) -> Outcome {
+    match status {
+        Some(status) => {
+            let reached = status
+                .confirmation_status
+                .as_deref()
+                .and_then(parse_level)
+                .unwrap_or(Commitment::Processed);
+            let levels = levels_between(tracked.level, reached);
+            if levels.is_empty() {
+                Outcome::Unchanged
+            } else {
+                Outcome::Promoted {
+                    levels,
+                    slot: status.slot,
+                }
+            }
+        }
+        None if finalized_slot > tracked.slot => Outcome::Dropped {
+            level: Commitment::Finalized,
+        },
+        None => Outcome::Unchanged,
+    }
+}
+
+/// Decide what happened to an account update from the blocks on each chain
+///
+/// Once a level's tip has passed the slot, the slot is either a block on
+/// that chain or was skipped by it.
+fn account_outcome(
+    slot: u64,
+    level: Commitment,
+    (confirmed_slot, confirmed): (u64, &HashSet<u64>),
+    (finalized_slot, finalized): (u64, &HashSet<u64>),
+) -> Outcome {
+    let (reached, on_chain) = if slot <= finalized_slot {
+        (Commitment::Finalized, finalized.contains(&slot))
+    } else if slot <= confirmed_slot && level < Commitment::Confirmed {
+        (Commitment::Confirmed, confirmed.contains(&slot))
+    } else {
+        return Outcome::Unchanged;
+    };
+
+    if on_chain {
+        Outcome::Promoted {
+            levels: levels_between(level, reached),
+            slot,
+        }
+    } else {
+        Outcome::Dropped { level: reached }
+    }
+}
+
+/// Update a tracked level; returns whether tracking is finished
+fn apply(outcome: &Outcome, level: &mut Commitment) -> bool {
+    match outcome {
+        Outcome::Unchanged => false,
+        Outcome::Promoted { levels, .. } => {
+            if let Some(&last) = levels.last() {
+                *level = last;
+            }
+            *level == Commitment::Finalized
+        }
+        Outcome::Dropped { .. } => true,
+    }
+}
+
+/// Commitment events for an outcome, one per level reached
+fn outcome_events(
+    outcome: &Outcome,
+    tracked_slot: u64,
+    event: impl Fn(CommitmentStatus, Commitment, u64) -> CommitmentEvent,
+) -> Vec<CommitmentEvent> {
+    match outcome {
+        Outcome::Unchanged => Vec::new(),
+        Outcome::Promoted { levels, slot } => levels
+            .iter()
+            .map(|level| event(CommitmentStatus::Promoted, *level, *slot))
+            .collect(),
+        Outcome::Dropped { level } => vec![event(CommitmentStatus::Dropped, *level, tracked_slot)],
+    }
+}
+
+/// Levels above `from` up to and including `to`
+fn levels_between(from: Commitment, to: Commitment) -> Vec<Commitment> {
+    [Commitment::Confirmed, Commitment::Finalized]
+        .into_iter()
+        .filter(|level| *level > from && *level <= to)
+        .collect()
+}
+
+/// Parse a `confirmationStatus` value
+fn parse_level(status: &str) -> Option<Commitment> {
+    match status {
+        "processed" => Some(Commitment::Processed),
+        "confirmed" => Some(Commitment::Confirmed),
+        "finalized" => Some(Commitment::Finalized),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_signature(slot: u64, level: Commitment) -> PendingSignature {
+        PendingSignature {
+            slot,
+            program_id: "program123".to_string(),
+            level,
+            subscription: 1,
+        }
+    }
+
+    fn status(slot: u64, confirmation_status: &str) -> SignatureStatus {
+        SignatureStatus {
+            slot,
+            confirmation_status: Some(confirmation_status.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_signature_promoted_through_each_level() {
+        let tracked = pending_signature(100, Commitment::Processed);
+
+        assert_eq!(
+            signature_outcome(&tracked, Some(&status(100, "processed")), 90),
+            Outcome::Unchanged
+        );
+        assert_eq!(
+            signature_outcome(&tracked, Some(&status(100, "finalized")), 132),
+            Outcome::Promoted {
+                levels: vec![Commitment::Confirmed, Commitment::Finalized],
+                slot: 100
+            }
+        );
+    }
+
+    #[test]
+    fn test_signature_dropped_after_finalized_root_passes() {
+        let tracked = pending_signature(100, Commitment::Confirmed);
+
+        assert_eq!(signature_outcome(&tracked, None, 100), Outcome::Unchanged);
+        assert_eq!(
+            signature_outcome(&tracked, None, 101),
+            Outcome::Dropped {
+                level: Commitment::Finalized
+            }
+        );
+    }
+
+    #[test]
+    fn test_account_outcome_follows_chain() {
+        let confirmed: HashSet<u64> = [100, 101, 103].into_iter().collect();
+        let finalized: HashSet<u64> = [100, 101].into_iter().collect();
+        let chains = |slot, level| {
+            account_outcome(slot, level, (103, &confirmed), (101, &finalized))
+        };
+
+        assert_eq!(
+            chains(103, Commitment::Processed),
+            Outcome::Promoted {
+                levels: vec![Commitment::Confirmed],
+                slot: 103
+            }
+        );
+        assert_eq!(
+            chains(102, Commitment::Processed),
+            Outcome::Dropped {
+                level: Commitment::Confirmed
+            }
+        );
+        assert_eq!(
+            chains(101, Commitment::Confirmed),
+            Outcome::Promoted {
+                levels: vec![Commitment::Finalized],
+                slot: 101
+            }
+        );
+        assert_eq!(chains(103, Commitment::Confirmed), Outcome::Unchanged);
+        assert_eq!(chains(104, Commitment::Processed), Outcome::Unchanged);
+    }
+
+    #[test]
+    fn test_apply_finishes_on_finalized_or_dropped() {
+        let mut level = Commitment::Processed;
+        let promoted = Outcome::Promoted {
+            levels: vec![Commitment::Confirmed],
+            slot: 100,
+        };
+        assert!(!apply(&promoted, &mut level));
+        assert_eq!(level, Commitment::Confirmed);
+
+        let finalized = Outcome::Promoted {
+            levels: vec![Commitment::Finalized],
+            slot: 100,
+        };
+        assert!(apply(&finalized, &mut level));
+        assert!(apply(
+            &Outcome::Dropped {
+                level: Commitment::Finalized
+            },
+            &mut level
+        ));
+    }
+
+    #[test]
+    fn test_track_ignores_duplicate_signatures() {
+        let tracker = CommitmentTracker::new(
+            "http://127.0.0.1:8899",
+            Commitment::Processed,
+            Duration::from_secs(2),
+        )
+        .unwrap();
+        let event = crate::event::LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "signature123".to_string(),
+            100,
+            "program123".to_string(),
+            vec![],
+        );
+
+        tracker.track(&Envelope::test(event.clone()));
+        tracker.track(&Envelope::test(event));
+
+        assert_eq!(tracker.pending.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,308 @@
+//! Log-content filtering before persistence
+//!
+//! Every rule that is configured must pass for a transaction to be stored;
+//! unset rules are skipped. Rejected transactions are counted per rule in
+//! `sol_events_filtered_total{reason}`.
+
+use crate::config::{Config, TransactionFilter};
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// Rule that rejected a transaction, used as the metric label
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    TransactionStatus,
+    Include,
+    Exclude,
+    ComputeUnits,
+    Programs,
+    AnchorEvents,
+}
+
+impl Rejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::TransactionStatus => "transaction_status",
+            Rejection::Include => "include",
+            Rejection::Exclude => "exclude",
+            Rejection::ComputeUnits => "compute_units",
+            Rejection::Programs => "programs",
+            Rejection::AnchorEvents => "anchor_events",
+        }
+    }
+}
+
+/// Substring and regex rules matched against individual log lines
+#[derive(Debug, Default)]
+struct LineRules {
+    substrings: Vec<String>,
+    regex: Option<Regex>,
+}
+
+impl LineRules {
+    fn new(substrings: &[String], regex: Option<&str>) -> Result<Self> {
+        let regex = regex
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid regex: {}", pattern)))
+            .transpose()?;
+        Ok(Self {
+            substrings: non_empty(substrings),
+            regex,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.substrings.is_empty() && self.regex.is_none()
+    }
+
+    /// Whether any line matches any rule
+    fn matches_any(&self, logs: &[String]) -> bool {
+        logs.iter().any(|line| {
+            self.substrings.iter().any(|s| line.contains(s.as_str()))
+                || self.regex.as_ref().is_some_and(|regex| regex.is_match(line))
+        })
+    }
+}
+
+/// Filter applied to log notifications before they reach the sinks
+#[derive(Debug, Default)]
+pub struct LogFilter {
+    transactions: Option<TransactionFilter>,
+    include: LineRules,
+    exclude: LineRules,
+    min_compute_units: Option<u64>,
+    required_programs: Vec<String>,
+    /// Discriminators of the accepted Anchor events
+    anchor_events: HashSet<[u8; 8]>,
+}
+
+impl LogFilter {
+    /// Build the filter from the `TRANSACTIONS`, `LOG_*`, `MIN_COMPUTE_UNITS`,
+    /// `REQUIRED_PROGRAMS` and `ANCHOR_EVENTS` settings
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Ok(Self {
+            transactions: Some(config.transactions),
+            include: LineRules::new(&config.log_include, config.log_include_regex.as_deref())?,
+            exclude: LineRules::new(&config.log_exclude, config.log_exclude_regex.as_deref())?,
+            min_compute_units: config.min_compute_units,
+            required_programs: non_empty(&config.required_programs),
+            anchor_events: non_empty(&config.anchor_events)
+                .iter()
+                .map(|name| anchor_event_discriminator(name))
+                .collect(),
+        })
+    }
+
+    /// Whether any rule beyond the transaction status is configured
+    pub fn has_content_rules(&self) -> bool {
+        !self.include.is_empty()
+            || !self.exclude.is_empty()
+            || self.min_compute_units.is_some()
+            || !self.required_programs.is_empty()
+            || !self.anchor_events.is_empty()
+    }
+
+    /// First rule rejecting the transaction, or `None` if it should be stored
+    pub fn check(&self, logs: &[String], failed: bool) -> Option<Rejection> {
+        if let Some(transactions) = self.transactions {
+            if !transactions.includes(failed) {
+                return Some(Rejection::TransactionStatus);
+            }
+        }
+        if !self.include.is_empty() && !self.include.matches_any(logs) {
+            return Some(Rejection::Include);
+        }
+        if !self.exclude.is_empty() && self.exclude.matches_any(logs) {
+            return Some(Rejection::Exclude);
+        }
+        if let Some(min) = self.min_compute_units {
+            if compute_units_consumed(logs) < min {
+                return Some(Rejection::ComputeUnits);
+            }
+        }
+        if !self.required_programs.is_empty() {
+            let invoked = invoked_programs(logs);
+            if !self.required_programs.iter().all(|p| invoked.contains(p.as_str())) {
+                return Some(Rejection::Programs);
+            }
+        }
+        if !self.anchor_events.is_empty() && !self.has_anchor_event(logs) {
+            return Some(Rejection::AnchorEvents);
+        }
+        None
+    }
+
+    /// Whether any `Program data:` line carries one of the accepted events
+    fn has_anchor_event(&self, logs: &[String]) -> bool {
+        logs.iter()
+            .filter_map(|line| line.strip_prefix("Program data: "))
+            .filter_map(|data| BASE64.decode(data.trim()).ok())
+            .any(|bytes| {
+                bytes
+                    .get(..8)
+                    .and_then(|prefix| <[u8; 8]>::try_from(prefix).ok())
+                    .is_some_and(|prefix| self.anchor_events.contains(&prefix))
+            })
+    }
+}
+
+/// Trimmed, non-empty entries of a comma-separated setting
+fn non_empty(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Anchor's event discriminator: the first 8 bytes of `sha256("event:<Name>")`
+fn anchor_event_discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("event:{}", name).as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+/// Program ids appearing in `Program <id> invoke [n]` lines
+pub fn invoked_programs(logs: &[String]) -> HashSet<&str> {
+    logs.iter()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("Program"), Some(id), Some("invoke")) => Some(id),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Compute units consumed by the transaction's top-level instructions
+///
+/// A program's `consumed` line includes its inner invocations, so only lines
+/// logged at invoke depth 1 are summed.
+fn compute_units_consumed(logs: &[String]) -> u64 {
+    let mut depth = 0usize;
+    let mut total = 0u64;
+    for line in logs {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["Program", _, "invoke", _] => depth += 1,
+            ["Program", _, "success"] | ["Program", _, "failed:", ..] => {
+                depth = depth.saturating_sub(1)
+            }
+            ["Program", _, "consumed", units, "of", _, "compute", "units"] if depth == 1 => {
+                total += units.parse::<u64>().unwrap_or(0);
+            }
+            _ => {}
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    /// Liquidation that CPIs into the token program
+    fn liquidation() -> Vec<String> {
+        logs(&[
+            "Program Lend1111111111111111111111111111111111111 invoke [1]",
+            "Program log: Instruction: Liquidate",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 4645 of 180000 compute units",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+            "Program Lend1111111111111111111111111111111111111 consumed 42000 of 200000 compute units",
+            "Program Lend1111111111111111111111111111111111111 success",
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 consumed 150 of 158000 compute units",
+            "Program ComputeBudget111111111111111111111111111111 success",
+        ])
+    }
+
+    #[test]
+    fn test_empty_filter_accepts_everything() {
+        let filter = LogFilter::default();
+        assert!(!filter.has_content_rules());
+        assert_eq!(filter.check(&liquidation(), true), None);
+    }
+
+    #[test]
+    fn test_transaction_status() {
+        let filter = LogFilter {
+            transactions: Some(TransactionFilter::Successful),
+            ..Default::default()
+        };
+        assert_eq!(filter.check(&liquidation(), false), None);
+        assert_eq!(filter.check(&liquidation(), true), Some(Rejection::TransactionStatus));
+    }
+
+    #[test]
+    fn test_include_and_exclude_rules() {
+        let filter = LogFilter {
+            include: LineRules::new(&["Instruction: Liquidate".to_string()], None).unwrap(),
+            exclude: LineRules::new(&[], Some(r"Instruction: (Deposit|Withdraw)")).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(filter.check(&liquidation(), false), None);
+        assert_eq!(
+            filter.check(&logs(&["Program log: Instruction: Deposit"]), false),
+            Some(Rejection::Include)
+        );
+
+        let both = logs(&["Program log: Instruction: Liquidate", "Program log: Instruction: Withdraw"]);
+        assert_eq!(filter.check(&both, false), Some(Rejection::Exclude));
+        assert!(LineRules::new(&[], Some("(")).is_err());
+    }
+
+    #[test]
+    fn test_compute_units_count_top_level_instructions() {
+        assert_eq!(compute_units_consumed(&liquidation()), 42_150);
+
+        let filter = LogFilter {
+            min_compute_units: Some(50_000),
+            ..Default::default()
+        };
+        assert_eq!(filter.check(&liquidation(), false), Some(Rejection::ComputeUnits));
+    }
+
+    #[test]
+    fn test_required_programs() {
+        let filter = LogFilter {
+            required_programs: vec![
+                "Lend1111111111111111111111111111111111111".to_string(),
+                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(filter.check(&liquidation(), false), None);
+        assert_eq!(
+            filter.check(&liquidation()[..2], false),
+            Some(Rejection::Programs)
+        );
+    }
+
+    #[test]
+    fn test_anchor_events() {
+        let filter = LogFilter {
+            anchor_events: [anchor_event_discriminator("LiquidationEvent")].into(),
+            ..Default::default()
+        };
+
+        let mut payload = anchor_event_discriminator("LiquidationEvent").to_vec();
+        payload.extend_from_slice(&[1, 2, 3, 4]);
+        let mut lines = liquidation();
+        assert_eq!(filter.check(&lines, false), Some(Rejection::AnchorEvents));
+
+        lines.insert(2, format!("Program data: {}", BASE64.encode(&payload)));
+        assert_eq!(filter.check(&lines, false), None);
+    }
+}
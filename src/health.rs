@@ -0,0 +1,355 @@
+//! Listener health incidents
+//!
+//! Samples the connection state, the cluster's slot and the error counter on
+//! an interval, raising a critical alert when a check has been failing for
+//! its threshold and a resolved alert with the same dedup key once it passes
+//! again.
+
+use crate::alert::{Alert, Severity};
+use crate::config::Config;
+use crate::http_rpc::HttpRpcClient;
+use crate::metrics::MetricsRegistry;
+use crate::notifier::Notifications;
+use anyhow::Result;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Condition watched by the monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Disconnected,
+    StalledSlot,
+    ErrorRate,
+}
+
+impl Check {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Check::Disconnected => "disconnected",
+            Check::StalledSlot => "stalled_slot",
+            Check::ErrorRate => "error_rate",
+        }
+    }
+}
+
+/// When each check starts failing
+#[derive(Debug, Clone)]
+pub struct Thresholds {
+    /// WebSocket down for this long
+    pub disconnected: Duration,
+    /// Cluster slot unchanged for this long
+    pub stalled_slot: Duration,
+    /// Errors per minute, averaged over `error_window`
+    pub errors_per_minute: f64,
+    pub error_window: Duration,
+}
+
+/// One observation of the listener
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub connected: bool,
+    /// Current cluster slot; `None` if it could not be fetched
+    pub slot: Option<u64>,
+    /// Value of `sol_errors_total`
+    pub errors: f64,
+}
+
+/// Failure tracking for one check
+#[derive(Debug, Default)]
+struct CheckState {
+    /// When the check started failing
+    failing_since: Option<Instant>,
+    firing: bool,
+}
+
+/// Check states driven by samples; separate from I/O so it can be tested
+#[derive(Debug)]
+pub struct HealthState {
+    thresholds: Thresholds,
+    /// Prefix of every dedup key
+    instance: String,
+    disconnected: CheckState,
+    stalled_slot: CheckState,
+    error_rate: CheckState,
+    /// Last slot seen and when it last changed
+    last_slot: Option<(u64, Instant)>,
+    /// Error counter samples covering the error window
+    errors: VecDeque<(Instant, f64)>,
+}
+
+impl HealthState {
+    pub fn new(thresholds: Thresholds, instance: &str) -> Self {
+        Self {
+            thresholds,
+            instance: instance.to_string(),
+            disconnected: CheckState::default(),
+            stalled_slot: CheckState::default(),
+            error_rate: CheckState::default(),
+            last_slot: None,
+            errors: VecDeque::new(),
+        }
+    }
+
+    /// Record a sample taken at `now`, returning alerts for checks that
+    /// started firing or resolved
+    pub fn observe(&mut self, sample: Sample, now: Instant) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+
+        // Disconnection
+        let limit = self.thresholds.disconnected;
+        let failing = !sample.connected;
+        if let Some(alert) = self.update(Check::Disconnected, failing, limit, now) {
+            alerts.push(alert);
+        }
+
+        // Stalled slot; an unavailable slot keeps the previous verdict
+        if let Some(slot) = sample.slot {
+            match self.last_slot {
+                Some((last, _)) if slot <= last => {}
+                _ => self.last_slot = Some((slot, now)),
+            }
+        }
+        let stalled = self
+            .last_slot
+            .is_some_and(|(_, changed)| now.duration_since(changed) >= self.thresholds.stalled_slot);
+        if let Some(alert) = self.update(Check::StalledSlot, stalled, Duration::ZERO, now) {
+            alerts.push(alert);
+        }
+
+        // Error rate over a full window
+        self.errors.push_back((now, sample.errors));
+        while self
+            .errors
+            .get(1)
+            .is_some_and(|(at, _)| now.duration_since(*at) >= self.thresholds.error_window)
+        {
+            self.errors.pop_front();
+        }
+        let rate = self.errors.front().and_then(|(at, errors)| {
+            let elapsed = now.duration_since(*at);
+            (elapsed >= self.thresholds.error_window)
+                .then(|| (sample.errors - errors) / elapsed.as_secs_f64() * 60.0)
+        });
+        let failing = rate.is_some_and(|rate| rate > self.thresholds.errors_per_minute);
+        if let Some(alert) = self.update(Check::ErrorRate, failing, Duration::ZERO, now) {
+            alerts.push(alert);
+        }
+
+        alerts
+    }
+
+    /// Move a check along; fires once it has failed for `after`
+    fn update(&mut self, check: Check, failing: bool, after: Duration, now: Instant) -> Option<Alert> {
+        let state = match check {
+            Check::Disconnected => &mut self.disconnected,
+            Check::StalledSlot => &mut self.stalled_slot,
+            Check::ErrorRate => &mut self.error_rate,
+        };
+
+        if !failing {
+            state.failing_since = None;
+            if !state.firing {
+                return None;
+            }
+            state.firing = false;
+            return Some(self.alert(check, true));
+        }
+
+        let since = *state.failing_since.get_or_insert(now);
+        if state.firing || now.duration_since(since) < after {
+            return None;
+        }
+        state.firing = true;
+        Some(self.alert(check, false))
+    }
+
+    fn alert(&self, check: Check, resolved: bool) -> Alert {
+        let title = match (check, resolved) {
+            (Check::Disconnected, false) => format!(
+                "WebSocket disconnected for over {}s",
+                self.thresholds.disconnected.as_secs()
+            ),
+            (Check::Disconnected, true) => "WebSocket reconnected".to_string(),
+            (Check::StalledSlot, false) => format!(
+                "Slot has not advanced for {}s",
+                self.thresholds.stalled_slot.as_secs()
+            ),
+            (Check::StalledSlot, true) => "Slot is advancing again".to_string(),
+            (Check::ErrorRate, false) => format!(
+                "Over {} errors per minute for {}s",
+                self.thresholds.errors_per_minute,
+                self.thresholds.error_window.as_secs()
+            ),
+            (Check::ErrorRate, true) => "Error rate back to normal".to_string(),
+        };
+
+        Alert {
+            dedup_key: Some(format!("solana-event-listener/{}/{}", self.instance, check.as_str())),
+            resolved,
+            ..Alert::new(Severity::Critical, format!("{}: {}", self.instance, title))
+        }
+    }
+}
+
+/// Samples listener health and sends incident alerts
+pub struct HealthMonitor {
+    rpc: HttpRpcClient,
+    commitment: String,
+    interval: Duration,
+    thresholds: Thresholds,
+    instance: String,
+}
+
+impl HealthMonitor {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let thresholds = Thresholds {
+            disconnected: Duration::from_secs(config.health_disconnected_secs),
+            stalled_slot: Duration::from_secs(config.health_stalled_slot_secs),
+            errors_per_minute: config.health_errors_per_minute,
+            error_window: Duration::from_secs(config.health_error_window_secs.max(1)),
+        };
+        Ok(Self {
+            rpc: HttpRpcClient::new(&config.http_rpc_url())?,
+            commitment: config.commitment.as_str().to_string(),
+            interval: Duration::from_secs(config.health_check_interval_secs.max(1)),
+            thresholds,
+            instance: config.instance_name(),
+        })
+    }
+
+    /// Sample health until cancelled
+    pub async fn run(&self, metrics: &MetricsRegistry, notifications: &Notifications) {
+        info!("Health checks every {:?}", self.interval);
+        let mut state = HealthState::new(self.thresholds.clone(), &self.instance);
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            let slot = match self
+                .rpc
+                .call::<u64>("getSlot", json!([{"commitment": self.commitment}]))
+                .await
+            {
+                Ok(slot) => Some(slot),
+                Err(e) => {
+                    debug!("getSlot failed: {:#}", e);
+                    None
+                }
+            };
+            let sample = Sample {
+                connected: metrics.ws_connected.get() >= 1.0,
+                slot,
+                errors: metrics.errors_total.get(),
+            };
+
+            for alert in state.observe(sample, Instant::now()) {
+                if alert.resolved {
+                    info!("Health check recovered: {}", alert.title);
+                } else {
+                    warn!("Health check failing: {}", alert.title);
+                }
+                notifications.send(alert);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> HealthState {
+        HealthState::new(
+            Thresholds {
+                disconnected: Duration::from_secs(60),
+                stalled_slot: Duration::from_secs(120),
+                errors_per_minute: 5.0,
+                error_window: Duration::from_secs(300),
+            },
+            "mainnet",
+        )
+    }
+
+    fn healthy(slot: u64, errors: f64) -> Sample {
+        Sample {
+            connected: true,
+            slot: Some(slot),
+            errors,
+        }
+    }
+
+    #[test]
+    fn test_disconnection_fires_after_threshold_and_resolves() {
+        let mut state = state();
+        let start = Instant::now();
+        let down = Sample {
+            connected: false,
+            ..healthy(1, 0.0)
+        };
+
+        assert!(state.observe(down, start).is_empty());
+        assert!(state.observe(down, start + Duration::from_secs(59)).is_empty());
+        let alerts = state.observe(
+            Sample { slot: Some(2), ..down },
+            start + Duration::from_secs(60),
+        );
+        assert_eq!(alerts.len(), 1);
+        assert!(!alerts[0].resolved);
+        assert_eq!(alerts[0].severity, Severity::Critical);
+        assert_eq!(
+            alerts[0].dedup_key.as_deref(),
+            Some("solana-event-listener/mainnet/disconnected")
+        );
+        // Still down: no repeat
+        assert!(state
+            .observe(Sample { slot: Some(3), ..down }, start + Duration::from_secs(70))
+            .is_empty());
+
+        let alerts = state.observe(healthy(4, 0.0), start + Duration::from_secs(80));
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].resolved);
+        assert_eq!(
+            alerts[0].dedup_key.as_deref(),
+            Some("solana-event-listener/mainnet/disconnected")
+        );
+    }
+
+    #[test]
+    fn test_stalled_slot() {
+        let mut state = state();
+        let start = Instant::now();
+
+        assert!(state.observe(healthy(100, 0.0), start).is_empty());
+        // An RPC failure neither advances nor stalls the slot
+        let unknown = Sample { slot: None, ..healthy(0, 0.0) };
+        assert!(state.observe(unknown, start + Duration::from_secs(60)).is_empty());
+        let alerts = state.observe(healthy(100, 0.0), start + Duration::from_secs(120));
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].title.contains("Slot has not advanced"));
+
+        let alerts = state.observe(healthy(101, 0.0), start + Duration::from_secs(130));
+        assert!(alerts[0].resolved);
+    }
+
+    #[test]
+    fn test_sustained_error_rate() {
+        let mut state = state();
+        let start = Instant::now();
+        let minute = Duration::from_secs(60);
+
+        // A burst within the first window does not count until the window is full
+        assert!(state.observe(healthy(1, 0.0), start).is_empty());
+        assert!(state.observe(healthy(2, 100.0), start + minute).is_empty());
+
+        // 100 errors over 5 minutes: 20 per minute
+        let alerts = state.observe(healthy(3, 100.0), start + minute * 5);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].dedup_key.as_deref().unwrap().ends_with("/error_rate"));
+
+        // The burst has left the window
+        let alerts = state.observe(healthy(4, 101.0), start + minute * 7);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].resolved);
+    }
+}
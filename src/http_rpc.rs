@@ -0,0 +1,92 @@
+//! Minimal HTTP JSON-RPC client for one-off queries
+//!
+//! Subscriptions run over the WebSocket; lookups such as `getBlockTime` or
+//! `getSignatureStatuses` go through this client instead.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+/// Timeout for a single request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// JSON-RPC response
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    result: Option<T>,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+/// JSON-RPC error, e.g. `-32004 Block not available for slot`
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// HTTP JSON-RPC client for a single endpoint
+pub struct HttpRpcClient {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpRpcClient {
+    /// Create a client for the endpoint `url`
+    pub fn new(url: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
+            client,
+            url: url.to_string(),
+        })
+    }
+
+    /// Call `method` and return its result
+    ///
+    /// A `null` result is an error unless `T` is an `Option`. Errors leave out
+    /// the URL, which may hold an API key.
+    pub async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+
+        let response: Response<T> = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("{} request failed", method))?
+            .error_for_status()
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("{} returned an error status", method))?
+            .json()
+            .await
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("Invalid {} response", method))?;
+
+        if let Some(error) = response.error {
+            anyhow::bail!("RPC error: {} (code: {})", error.message, error.code);
+        }
+        match response.result {
+            Some(result) => Ok(result),
+            // Lets `T = Option<_>` accept `null`
+            None => serde_json::from_value(serde_json::Value::Null)
+                .with_context(|| format!("{} returned no result", method)),
+        }
+    }
+}
@@ -1,16 +1,44 @@
+mod alert;
+mod alert_manager;
+mod block_time;
+mod commitment;
 mod config;
 mod event;
+mod filter;
+mod health;
+mod http_rpc;
 mod metrics;
 mod notifier;
+mod pipeline;
+mod plugin;
 mod rpc;
+mod rules;
+mod script;
 mod storage;
 
 use anyhow::Result;
-use config::Config;
-use storage::JsonlWriter;
-use tracing::info;
+use block_time::BlockTimeCache;
+use commitment::CommitmentTracker;
+use config::{Commitment, Config};
+use event::{redact_endpoint, EnvelopeSource};
+use filter::LogFilter;
+use health::HealthMonitor;
+use notifier::Notifications;
+use pipeline::Pipeline;
+use plugin::PluginRegistry;
+use script::ScriptHook;
+use std::path::Path;
+use std::time::Duration;
+use storage::SinkSet;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+/// How long shutdown waits for sinks to write their queued events
+const SINK_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long shutdown waits for queued alerts
+const NOTIFICATION_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing subscriber with environment filter
@@ -31,26 +59,132 @@ async fn main() -> Result<()> {
     let metrics = metrics::MetricsRegistry::default();
     info!("Metrics registry initialized");
 
-    // Spawn metrics server
+    // Initialize storage
+    let sinks = SinkSet::from_config(&config, metrics.clone()).await?;
+    info!("Storage initialized: sinks={:?}", sinks.names());
+
+    let source = EnvelopeSource::new(&config.ws_url, config.commitment.as_str());
+
+    let block_times = if config.resolve_block_time {
+        let url = config.http_rpc_url();
+        info!("Resolving block times via {}", redact_endpoint(&url));
+        Some(BlockTimeCache::new(&url)?)
+    } else {
+        None
+    };
+
+    let tracker = match (config.track_commitment, config.commitment) {
+        (false, _) => None,
+        (true, Commitment::Finalized) => {
+            warn!("TRACK_COMMITMENT has no effect at finalized commitment");
+            None
+        }
+        (true, level) => {
+            info!("Tracking commitment of {} events", level.as_str());
+            let poll_interval = Duration::from_millis(config.commitment_poll_interval_ms.max(1));
+            Some(CommitmentTracker::new(&config.http_rpc_url(), level, poll_interval)?)
+        }
+    };
+
+    let log_filter = LogFilter::from_config(&config)?;
+    if matches!(config.mode, config::Mode::Logs) {
+        info!(
+            "Log filter: transactions={}, content_rules={}",
+            config.transactions.as_str(),
+            log_filter.has_content_rules()
+        );
+    }
+
+    let script = match &config.script_path {
+        Some(path) => {
+            info!("Loading script hook from {}", path);
+            Some(ScriptHook::load(Path::new(path), config.script_max_operations)?)
+        }
+        None => None,
+    };
+
+    let plugins = match &config.plugin_dir {
+        Some(dir) => {
+            let plugins = PluginRegistry::load_dir(Path::new(dir), config.plugin_fuel)?;
+            if plugins.is_empty() {
+                warn!("No decoder plugins found in {}", dir);
+            }
+            Some(plugins)
+        }
+        None => None,
+    };
+
+    let notifications = Notifications::from_config(&config, metrics.clone())?;
+
+    // Spawn metrics server, which also serves the silence API
     let metrics_addr = config.metrics_socket_addr()?;
-    let _metrics_handle = metrics.spawn_server(metrics_addr);
+    let silence_api = alert_manager::routes(notifications.silences());
+    let _metrics_handle = metrics.spawn_server(metrics_addr, silence_api);
     info!("Metrics server spawned on {}", metrics_addr);
 
-    // Initialize storage
-    let writer = JsonlWriter::new(&config.event_log_path);
-    info!("Storage initialized: {}", config.event_log_path);
+    let health = if config.health_alerts && !notifications.is_empty() {
+        Some(HealthMonitor::from_config(&config)?)
+    } else {
+        None
+    };
 
-    // Route to appropriate subscription mode
-    match config.mode {
-        config::Mode::Logs => {
-            info!("Starting logs subscription mode");
-            rpc::run_logs_subscribe(&config, writer, metrics).await?;
+    let pipeline = Pipeline {
+        source: &source,
+        block_times: block_times.as_ref(),
+        tracker: tracker.as_ref(),
+        log_filter: &log_filter,
+        script: script.as_ref(),
+        plugins: plugins.as_ref(),
+        notifications: &notifications,
+        sinks: &sinks,
+        metrics: &metrics,
+    };
+
+    // Route to appropriate subscription mode, stopping on CTRL+C
+    let subscription = async {
+        match config.mode {
+            config::Mode::Logs => {
+                info!("Starting logs subscription mode");
+                rpc::run_logs_subscribe(&config, &pipeline).await
+            }
+            config::Mode::Account => {
+                info!("Starting account subscription mode");
+                rpc::run_account_subscribe(&config, &pipeline).await
+            }
         }
-        config::Mode::Account => {
-            info!("Starting account subscription mode");
-            rpc::run_account_subscribe(&config, writer, metrics).await?;
+    };
+
+    // Poll for promotions and dropped forks alongside the subscription
+    let commitment = async {
+        match &tracker {
+            Some(tracker) => tracker.run(&pipeline).await,
+            None => std::future::pending().await,
         }
-    }
+    };
+
+    // Raise incidents while the listener is unhealthy
+    let health_checks = async {
+        match &health {
+            Some(health) => health.run(&metrics, &notifications).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    let result = tokio::select! {
+        result = subscription => result,
+        _ = commitment => Ok(()),
+        _ = health_checks => Ok(()),
+        _ = tokio::signal::ctrl_c() => {
+            info!("Received CTRL+C, shutting down");
+            Ok(())
+        }
+    };
+
+    // Flush sinks so compressed segments and buffered batches are finalized
+    sinks.close(SINK_DRAIN_TIMEOUT).await;
+
+    // Give queued alerts a chance to go out
+    notifications.close(NOTIFICATION_DRAIN_TIMEOUT).await;
 
-    Ok(())
+    result
 }
@@ -7,7 +7,7 @@ use axum::{
     routing::get,
     Router,
 };
-use prometheus::{Counter, Gauge, Opts, Registry, TextEncoder};
+use prometheus::{Counter, CounterVec, Gauge, Histogram, HistogramOpts, HistogramVec, Opts, Registry, TextEncoder};
 use std::net::SocketAddr;
 use tracing::{error, info};
 
@@ -18,8 +18,31 @@ pub struct MetricsRegistry {
     pub events_total: Counter,
     /// Total number of errors encountered
     pub errors_total: Counter,
+    /// Events discarded by filters before reaching the sinks, labeled by rule
+    pub events_filtered_total: CounterVec,
+    /// Script hook calls that failed or exceeded their budget
+    pub script_errors_total: Counter,
+    /// Failed decoder plugin calls, labeled by plugin
+    pub plugin_errors_total: CounterVec,
+    /// Alerts seen by the alert manager, labeled by result (sent, silenced,
+    /// duplicate, grouped, summary, dropped)
+    pub alerts_total: CounterVec,
+    /// Alert deliveries, labeled by notifier and result (sent, failed, dropped)
+    pub notifications_total: CounterVec,
+    /// Time spent delivering an alert, labeled by notifier
+    pub notification_duration_seconds: HistogramVec,
     /// WebSocket connection status (1=connected, 0=disconnected)
     pub ws_connected: Gauge,
+    /// Failed sink writes, labeled by sink name
+    pub sink_errors_total: CounterVec,
+    /// Asynchronous deliveries that failed after a sink accepted the event
+    pub sink_delivery_failures_total: CounterVec,
+    /// Segment uploads to object storage, labeled by result
+    pub segment_uploads_total: CounterVec,
+    /// Receipt time minus on-chain block time of notifications
+    pub notification_latency_seconds: Histogram,
+    /// Commitment events emitted, labeled by status (promoted, dropped)
+    pub commitment_events_total: CounterVec,
     /// Inner Prometheus registry
     registry: Registry,
 }
@@ -41,6 +64,56 @@ impl MetricsRegistry {
         let errors_total = Counter::with_opts(errors_total_opts)?;
         registry.register(Box::new(errors_total.clone()))?;
 
+        // Register filtered events counter
+        let events_filtered_total = CounterVec::new(
+            Opts::new("events_filtered_total", "Total number of events discarded by filters")
+                .namespace("sol"),
+            &["reason"],
+        )?;
+        registry.register(Box::new(events_filtered_total.clone()))?;
+
+        // Register script error counter
+        let script_errors_total = Counter::with_opts(
+            Opts::new("script_errors_total", "Total number of failed script hook calls")
+                .namespace("sol"),
+        )?;
+        registry.register(Box::new(script_errors_total.clone()))?;
+
+        // Register per-plugin error counter
+        let plugin_errors_total = CounterVec::new(
+            Opts::new("plugin_errors_total", "Total number of failed decoder plugin calls")
+                .namespace("sol"),
+            &["plugin"],
+        )?;
+        registry.register(Box::new(plugin_errors_total.clone()))?;
+
+        // Register alert manager outcome counter
+        let alerts_total = CounterVec::new(
+            Opts::new("alerts_total", "Total number of alerts by alert manager outcome")
+                .namespace("sol"),
+            &["result"],
+        )?;
+        registry.register(Box::new(alerts_total.clone()))?;
+
+        // Register notification counter
+        let notifications_total = CounterVec::new(
+            Opts::new("notifications_total", "Total number of alert deliveries")
+                .namespace("sol"),
+            &["notifier", "result"],
+        )?;
+        registry.register(Box::new(notifications_total.clone()))?;
+
+        // Register per-notifier delivery duration histogram, retries included
+        let notification_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "notification_duration_seconds",
+                "Time spent delivering an alert, including retries",
+            )
+            .namespace("sol"),
+            &["notifier"],
+        )?;
+        registry.register(Box::new(notification_duration_seconds.clone()))?;
+
         // Register ws_connected gauge
         let ws_connected_opts = Opts::new(
             "sol_ws_connected",
@@ -50,10 +123,74 @@ impl MetricsRegistry {
         let ws_connected = Gauge::with_opts(ws_connected_opts)?;
         registry.register(Box::new(ws_connected.clone()))?;
 
+        // Register per-sink error counter
+        let sink_errors_total = CounterVec::new(
+            Opts::new("sink_errors_total", "Total number of failed event sink writes")
+                .namespace("sol"),
+            &["sink"],
+        )?;
+        registry.register(Box::new(sink_errors_total.clone()))?;
+
+        // Register per-sink delivery failure counter
+        let sink_delivery_failures_total = CounterVec::new(
+            Opts::new(
+                "sink_delivery_failures_total",
+                "Total number of events a sink accepted but failed to deliver",
+            )
+            .namespace("sol"),
+            &["sink"],
+        )?;
+        registry.register(Box::new(sink_delivery_failures_total.clone()))?;
+
+        // Register segment upload counter
+        let segment_uploads_total = CounterVec::new(
+            Opts::new(
+                "segment_uploads_total",
+                "Total number of JSONL segment uploads to object storage",
+            )
+            .namespace("sol"),
+            &["result"],
+        )?;
+        registry.register(Box::new(segment_uploads_total.clone()))?;
+
+        // Register notification latency histogram; block times have
+        // one-second resolution, so finer buckets would be noise
+        let notification_latency_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "notification_latency_seconds",
+                "Notification receipt time minus on-chain block time",
+            )
+            .namespace("sol")
+            .buckets(vec![1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0]),
+        )?;
+        registry.register(Box::new(notification_latency_seconds.clone()))?;
+
+        // Register commitment event counter
+        let commitment_events_total = CounterVec::new(
+            Opts::new(
+                "commitment_events_total",
+                "Total number of commitment promotions and drops observed",
+            )
+            .namespace("sol"),
+            &["status"],
+        )?;
+        registry.register(Box::new(commitment_events_total.clone()))?;
+
         Ok(Self {
             events_total,
             errors_total,
+            events_filtered_total,
+            script_errors_total,
+            plugin_errors_total,
+            alerts_total,
+            notifications_total,
+            notification_duration_seconds,
             ws_connected,
+            sink_errors_total,
+            sink_delivery_failures_total,
+            segment_uploads_total,
+            notification_latency_seconds,
+            commitment_events_total,
             registry,
         })
     }
@@ -87,8 +224,8 @@ impl MetricsRegistry {
         Ok(())
     }
 
-    /// Spawn metrics server in background task
-    pub fn spawn_server(&self, addr: SocketAddr) -> tokio::task::JoinHandle<()> {
+    /// Spawn metrics server in background task, also serving `routes`
+    pub fn spawn_server(&self, addr: SocketAddr, routes: Router) -> tokio::task::JoinHandle<()> {
         let registry = self.registry.clone();
         tokio::spawn(async move {
             let app = Router::new().route("/metrics", get(metrics_handler));
@@ -108,6 +245,7 @@ impl MetricsRegistry {
             let server = axum::serve(
                 listener,
                 app.with_state(app_state)
+                    .merge(routes)
                     .into_make_service_with_connect_info::<SocketAddr>(),
             )
             .await;
@@ -175,6 +313,9 @@ mod tests {
 
         registry.ws_connected.set(1.0);
         assert_eq!(registry.ws_connected.get(), 1.0);
+
+        registry.sink_errors_total.with_label_values(&["jsonl"]).inc();
+        assert_eq!(registry.sink_errors_total.with_label_values(&["jsonl"]).get(), 1.0);
     }
 
     #[tokio::test]
@@ -183,7 +324,7 @@ mod tests {
         let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
         
         // Spawn the server in a background task
-        let handle = registry.spawn_server(addr);
+        let handle = registry.spawn_server(addr, Router::new());
         
         // Give the server a moment to start
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
@@ -1,38 +1,778 @@
-//! Event notification system (future implementation)
+//! Alert notifications
 //!
-//! TODO: Implement webhook notifications for event thresholds
-//! TODO: Add Slack integration
-//! TODO: Support configurable notification rules
+//! Alerts go through the [`AlertManager`] first, then to every notifier's own
+//! queue and background worker, so a slow or failing notifier neither stalls
+//! the subscription nor delays the others.
 
-use anyhow::Result;
+mod discord;
+mod email;
+mod explorer;
+mod http;
+mod opsgenie;
+mod pagerduty;
+mod slack;
+mod telegram;
+mod text;
+mod webhook;
 
-/// Trait for notifying about events
-#[allow(dead_code)]
+pub use discord::DiscordNotifier;
+pub use email::{EmailNotifier, EmailOptions};
+pub use explorer::{Explorer, DEFAULT_ADDRESS_URL, DEFAULT_TX_URL};
+pub use http::JsonPoster;
+pub use opsgenie::{OpsgenieNotifier, DEFAULT_OPSGENIE_URL};
+pub use pagerduty::{PagerDutyNotifier, DEFAULT_PAGERDUTY_URL};
+pub use slack::SlackNotifier;
+pub use telegram::TelegramNotifier;
+pub use webhook::WebhookNotifier;
+
+use crate::alert::Alert;
+use crate::alert_manager::{AlertManager, AlertPolicy, Routed, Silences};
+use crate::config::{Config, NotifierKind};
+use crate::event::{Envelope, EventType};
+use crate::metrics::MetricsRegistry;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use crate::rules::RuleSet;
+use regex::Regex;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Alerts buffered per notifier, and for the alert manager, before new ones are dropped
+const QUEUE_CAPACITY: usize = 1024;
+
+/// How often the alert manager looks for groups to summarize
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Destination for alerts
+#[async_trait]
 pub trait Notifier: Send + Sync {
-    /// Send a notification message
-    async fn notify(&self, message: &str) -> Result<()>;
+    /// Short name used in logs and metric labels
+    fn name(&self) -> &str;
+
+    /// Deliver a single alert, or add it to the current batch
+    async fn notify(&self, alert: &Alert) -> Result<()>;
+
+    /// Whether alerts open incidents keyed by their dedup key; such notifiers
+    /// don't get the alert manager's keyless summaries
+    fn tracks_incidents(&self) -> bool {
+        false
+    }
+
+    /// How often `flush` should run, for notifiers that batch alerts
+    fn flush_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Send the alerts batched so far, returning how many were sent
+    async fn flush(&self) -> Result<usize> {
+        Ok(0)
+    }
 }
 
 /// Stub notifier that does nothing
 #[allow(dead_code)]
 pub struct StubNotifier;
 
+#[async_trait]
 impl Notifier for StubNotifier {
-    async fn notify(&self, _message: &str) -> Result<()> {
+    fn name(&self) -> &str {
+        "stub"
+    }
+
+    async fn notify(&self, _alert: &Alert) -> Result<()> {
         // No-op for now
         Ok(())
     }
 }
 
+/// Which stored events raise an alert
+#[derive(Debug)]
+pub struct EventMatcher {
+    types: Vec<EventType>,
+    /// Matched against the event's JSON
+    pattern: Option<Regex>,
+}
+
+impl EventMatcher {
+    pub fn new(types: Vec<EventType>, pattern: Option<&str>) -> Result<Self> {
+        let pattern = pattern
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid regex: {}", pattern)))
+            .transpose()?;
+        Ok(Self { types, pattern })
+    }
+
+    pub fn matches(&self, envelope: &Envelope) -> bool {
+        if !self.types.contains(&envelope.event_type) {
+            return false;
+        }
+        match &self.pattern {
+            Some(pattern) => serde_json::to_string(&envelope.event)
+                .is_ok_and(|json| pattern.is_match(&json)),
+            None => true,
+        }
+    }
+}
+
+/// Body and event details of an alert as a JSON object
+fn details_json(alert: &Alert) -> serde_json::Map<String, serde_json::Value> {
+    let details = alert.details();
+    let mut json = serde_json::Map::new();
+    if !alert.body.is_empty() {
+        json.insert("body".to_string(), alert.body.clone().into());
+    }
+    let named = [
+        ("signature", details.signature),
+        ("account", details.account),
+        ("program", details.program),
+        ("event", details.event_name),
+    ];
+    for (key, value) in named {
+        if let Some(value) = value {
+            json.insert(key.to_string(), value.into());
+        }
+    }
+    if let Some(slot) = details.slot {
+        json.insert("slot".to_string(), slot.into());
+    }
+    for (key, value) in details.fields {
+        json.entry(key).or_insert(value.into());
+    }
+    json
+}
+
+/// At most `max` characters of `text`, ending in an ellipsis when cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Queue feeding one notifier's worker
+struct Channel {
+    name: String,
+    /// The notifier tracks incidents, so summaries skip it
+    incidents: bool,
+    sender: mpsc::Sender<Arc<Alert>>,
+}
+
+/// Fan-out of alerts to several notifiers through the alert manager
+///
+/// Deliveries are counted per notifier in `sol_notifications_total` and timed
+/// in `sol_notification_duration_seconds`.
+pub struct Notifications {
+    matcher: EventMatcher,
+    /// Replace `matcher` when set
+    rules: Option<RuleSet>,
+    /// Names of the configured notifiers
+    names: Vec<String>,
+    /// Queue feeding the alert manager
+    sender: mpsc::Sender<Routed>,
+    silences: Arc<Silences>,
+    /// Alert manager task, then one worker per notifier
+    tasks: Vec<JoinHandle<()>>,
+    metrics: MetricsRegistry,
+}
+
+impl Notifications {
+    /// Start the alert manager and a worker for each notifier
+    pub fn new(
+        notifiers: Vec<Box<dyn Notifier>>,
+        matcher: EventMatcher,
+        policy: AlertPolicy,
+        metrics: MetricsRegistry,
+    ) -> Self {
+        let mut channels = Vec::new();
+        let mut tasks = Vec::new();
+        for notifier in notifiers {
+            let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+            channels.push(Channel {
+                name: notifier.name().to_string(),
+                incidents: notifier.tracks_incidents(),
+                sender,
+            });
+            tasks.push(tokio::spawn(deliver(notifier, receiver, metrics.clone())));
+        }
+
+        let names = channels.iter().map(|channel| channel.name.clone()).collect();
+        let silences = Arc::new(Silences::default());
+        let manager = AlertManager::new(policy, silences.clone(), metrics.clone());
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tasks.insert(0, tokio::spawn(route(manager, receiver, channels, metrics.clone())));
+
+        Self {
+            matcher,
+            rules: None,
+            names,
+            sender,
+            silences,
+            tasks,
+            metrics,
+        }
+    }
+
+    /// Raise alerts from `rules` instead of the event matcher
+    pub fn with_rules(mut self, rules: RuleSet) -> Result<Self> {
+        for name in rules.notifier_names() {
+            anyhow::ensure!(
+                self.names.iter().any(|known| known == name),
+                "Rules route to notifier {}, which is not in NOTIFIERS",
+                name
+            );
+        }
+        self.rules = Some(rules);
+        Ok(self)
+    }
+
+    /// Build the notifiers listed in `NOTIFIERS`
+    pub fn from_config(config: &Config, metrics: MetricsRegistry) -> Result<Self> {
+        let poster = JsonPoster::new(
+            Duration::from_secs(config.notify_timeout_secs.max(1)),
+            config.notify_max_retries,
+        )?;
+        let explorer = Explorer::new(
+            &config.explorer_tx_url,
+            &config.explorer_address_url,
+            &config.cluster,
+        );
+        let required = |value: &Option<String>, name: &str, kind: NotifierKind| {
+            value
+                .clone()
+                .filter(|value| !value.is_empty())
+                .with_context(|| format!("NOTIFIERS={} requires {}", kind.as_str(), name))
+        };
+
+        let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+        for &kind in &config.notifiers {
+            let notifier: Box<dyn Notifier> = match kind {
+                NotifierKind::Webhook => Box::new(WebhookNotifier::new(
+                    poster.clone(),
+                    required(&config.webhook_url, "WEBHOOK_URL", kind)?,
+                    config.webhook_secret.clone(),
+                )),
+                NotifierKind::Slack => Box::new(SlackNotifier::new(
+                    poster.clone(),
+                    required(&config.slack_webhook_url, "SLACK_WEBHOOK_URL", kind)?,
+                    explorer.clone(),
+                )),
+                NotifierKind::Discord => Box::new(DiscordNotifier::new(
+                    poster.clone(),
+                    required(&config.discord_webhook_url, "DISCORD_WEBHOOK_URL", kind)?,
+                    explorer.clone(),
+                )),
+                NotifierKind::Telegram => Box::new(TelegramNotifier::new(
+                    poster.clone(),
+                    &config.telegram_api_url,
+                    &required(&config.telegram_bot_token, "TELEGRAM_BOT_TOKEN", kind)?,
+                    required(&config.telegram_chat_id, "TELEGRAM_CHAT_ID", kind)?,
+                    explorer.clone(),
+                )),
+                NotifierKind::Email => Box::new(EmailNotifier::new(
+                    EmailOptions {
+                        host: required(&config.smtp_host, "SMTP_HOST", kind)?,
+                        port: config.smtp_port,
+                        tls: config.smtp_tls,
+                        username: config.smtp_username.clone(),
+                        password: config.smtp_password.clone(),
+                        from: required(&config.email_from, "EMAIL_FROM", kind)?,
+                        to: config.email_to.clone(),
+                        subject_prefix: config.email_subject_prefix.clone(),
+                        timeout: Duration::from_secs(config.notify_timeout_secs.max(1)),
+                        digest_window: config.email_digest_secs.map(|secs| Duration::from_secs(secs.max(1))),
+                    },
+                    explorer.clone(),
+                )?),
+                NotifierKind::Pagerduty => Box::new(PagerDutyNotifier::new(
+                    poster.clone(),
+                    config.pagerduty_url.clone(),
+                    required(&config.pagerduty_routing_key, "PAGERDUTY_ROUTING_KEY", kind)?,
+                    config.instance_name(),
+                )),
+                NotifierKind::Opsgenie => Box::new(OpsgenieNotifier::new(
+                    poster.clone(),
+                    &config.opsgenie_api_url,
+                    &required(&config.opsgenie_api_key, "OPSGENIE_API_KEY", kind)?,
+                    config.instance_name(),
+                )?),
+            };
+            info!("Notifier enabled: {}", notifier.name());
+            notifiers.push(notifier);
+        }
+
+        let matcher = EventMatcher::new(
+            config.notify_event_types.clone(),
+            config.notify_match.as_deref(),
+        )?;
+        let policy = AlertPolicy {
+            dedup_window: Duration::from_secs(config.alert_dedup_secs),
+            cooldown: Duration::from_secs(config.alert_cooldown_secs),
+        };
+        let notifications = Self::new(notifiers, matcher, policy, metrics);
+
+        match &config.notify_rules_path {
+            Some(path) => {
+                let rules = RuleSet::load(Path::new(path))?;
+                info!("Loaded {} notification rules from {}", rules.len(), path);
+                notifications.with_rules(rules)
+            }
+            None => Ok(notifications),
+        }
+    }
+
+    /// Whether no notifier is configured
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Silences applied by the alert manager, for the silence API
+    pub fn silences(&self) -> Arc<Silences> {
+        self.silences.clone()
+    }
+
+    /// Raise alerts for a stored event that matches; never waits
+    pub fn offer(&self, envelope: &Envelope) {
+        if self.is_empty() {
+            return;
+        }
+        match &self.rules {
+            Some(rules) => {
+                for routed in rules.evaluate(envelope, Instant::now()) {
+                    self.route(routed);
+                }
+            }
+            None if self.matcher.matches(envelope) => self.send(Alert::from_envelope(envelope)),
+            None => {}
+        }
+    }
+
+    /// Queue an alert for every notifier; never waits
+    pub fn send(&self, alert: Alert) {
+        self.route(Routed::new(alert, Vec::new()));
+    }
+
+    /// Queue an alert for the alert manager; never waits
+    fn route(&self, routed: Routed) {
+        if self.sender.try_send(routed).is_err() {
+            warn!("Alert manager is backed up, dropping alert");
+            self.metrics.alerts_total.with_label_values(&["dropped"]).inc();
+        }
+    }
+
+    /// Stop accepting alerts and wait up to `timeout` for queued ones and
+    /// pending summaries
+    pub async fn close(self, timeout: Duration) {
+        drop(self.sender);
+        let drain = futures::future::join_all(self.tasks);
+        if tokio::time::timeout(timeout, drain).await.is_err() {
+            warn!("Gave up on pending notifications after {:?}", timeout);
+        }
+    }
+}
+
+/// Pass alerts through the alert manager to the notifier queues until the
+/// manager's queue is closed, then send the summaries still pending
+async fn route(
+    mut manager: AlertManager,
+    mut receiver: mpsc::Receiver<Routed>,
+    channels: Vec<Channel>,
+    metrics: MetricsRegistry,
+) {
+    let mut summaries = tokio::time::interval(SUMMARY_INTERVAL);
+    loop {
+        let ready = tokio::select! {
+            routed = receiver.recv() => {
+                let Some(routed) = routed else { break };
+                manager.admit(routed, Instant::now())
+            }
+            _ = summaries.tick() => manager.due(Instant::now()),
+        };
+        for routed in ready {
+            dispatch(&channels, routed, &metrics);
+        }
+    }
+    for routed in manager.drain() {
+        dispatch(&channels, routed, &metrics);
+    }
+}
+
+/// Queue an alert for its notifiers, or all when it names none; never waits
+fn dispatch(channels: &[Channel], routed: Routed, metrics: &MetricsRegistry) {
+    let alert = Arc::new(routed.alert);
+    let names = &routed.notifiers;
+    let targets = channels
+        .iter()
+        .filter(|channel| names.is_empty() || names.contains(&channel.name))
+        .filter(|channel| !(routed.summary && channel.incidents));
+    for channel in targets {
+        if channel.sender.try_send(alert.clone()).is_err() {
+            warn!("Notifier {} is backed up, dropping alert", channel.name);
+            metrics
+                .notifications_total
+                .with_label_values(&[&channel.name, "dropped"])
+                .inc();
+        }
+    }
+}
+
+/// Deliver alerts to one notifier until its queue is closed
+///
+/// Batching notifiers are flushed on their interval and once more at the end;
+/// their alerts count as `batched` and each batch as `sent` or `failed`.
+async fn deliver(
+    notifier: Box<dyn Notifier>,
+    mut receiver: mpsc::Receiver<Arc<Alert>>,
+    metrics: MetricsRegistry,
+) {
+    let name = notifier.name().to_string();
+    let mut flushes = notifier
+        .flush_interval()
+        .map(|period| tokio::time::interval_at(tokio::time::Instant::now() + period, period));
+    let accepted = if flushes.is_some() { "batched" } else { "sent" };
+
+    let record = |started: Instant, result: &str| {
+        metrics
+            .notification_duration_seconds
+            .with_label_values(&[&name])
+            .observe(started.elapsed().as_secs_f64());
+        metrics
+            .notifications_total
+            .with_label_values(&[&name, result])
+            .inc();
+    };
+
+    loop {
+        let flush_due = async {
+            match flushes.as_mut() {
+                Some(flushes) => flushes.tick().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            alert = receiver.recv() => {
+                let Some(alert) = alert else { break };
+                let started = Instant::now();
+                let result = match notifier.notify(&alert).await {
+                    Ok(()) => accepted,
+                    Err(e) => {
+                        warn!("Notifier {} failed to deliver {} alert: {:#}", name, alert.severity.as_str(), e);
+                        "failed"
+                    }
+                };
+                record(started, result);
+            }
+            _ = flush_due => flush(notifier.as_ref(), &record).await,
+        }
+    }
+    if flushes.is_some() {
+        flush(notifier.as_ref(), &record).await;
+    }
+}
+
+/// Flush a batching notifier, recording non-empty batches
+async fn flush(notifier: &dyn Notifier, record: &impl Fn(Instant, &str)) {
+    let started = Instant::now();
+    match notifier.flush().await {
+        Ok(0) => {}
+        Ok(_) => record(started, "sent"),
+        Err(e) => {
+            warn!("Notifier {} failed to send batch: {:#}", notifier.name(), e);
+            record(started, "failed");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::alert::Severity;
+    use crate::event::{AccountEvent, LogEvent};
+    use crate::rules::{Condition, RuleConfig};
+    use std::sync::Mutex;
+
+    fn policy() -> AlertPolicy {
+        AlertPolicy {
+            dedup_window: Duration::from_secs(300),
+            cooldown: Duration::from_secs(60),
+        }
+    }
 
     #[tokio::test]
     async fn test_stub_notifier() {
         let notifier = StubNotifier;
         // Should not panic or error
-        notifier.notify("test message").await.unwrap();
+        notifier
+            .notify(&Alert::new(Severity::Info, "test message"))
+            .await
+            .unwrap();
+    }
+
+    /// Records alert titles, optionally failing every delivery
+    struct Recorder {
+        name: &'static str,
+        fail: bool,
+        incidents: bool,
+        titles: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn tracks_incidents(&self) -> bool {
+            self.incidents
+        }
+
+        async fn notify(&self, alert: &Alert) -> Result<()> {
+            self.titles.lock().unwrap().push(alert.title.clone());
+            if self.fail {
+                anyhow::bail!("receiver down");
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failing_notifier_is_isolated() {
+        let metrics = MetricsRegistry::default();
+        let good = Arc::new(Mutex::new(Vec::new()));
+        let bad = Arc::new(Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(Recorder {
+                name: "bad",
+                fail: true,
+                incidents: false,
+                titles: bad.clone(),
+            }),
+            Box::new(Recorder {
+                name: "good",
+                fail: false,
+                incidents: false,
+                titles: good.clone(),
+            }),
+        ];
+        let matcher = EventMatcher::new(vec![EventType::Log], None).unwrap();
+        let notifications = Notifications::new(notifiers, matcher, policy(), metrics.clone());
+
+        notifications.send(Alert::new(Severity::Warning, "first"));
+        notifications.send(Alert::new(Severity::Warning, "second"));
+        notifications.close(Duration::from_secs(5)).await;
+
+        assert_eq!(*good.lock().unwrap(), vec!["first", "second"]);
+        assert_eq!(bad.lock().unwrap().len(), 2);
+        let count = |notifier: &str, result: &str| {
+            metrics
+                .notifications_total
+                .with_label_values(&[notifier, result])
+                .get()
+        };
+        assert_eq!(count("good", "sent"), 2.0);
+        assert_eq!(count("bad", "failed"), 2.0);
     }
-}
 
+    #[tokio::test]
+    async fn test_rules_route_to_named_notifiers() {
+        let metrics = MetricsRegistry::default();
+        let slack = Arc::new(Mutex::new(Vec::new()));
+        let pagerduty = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |name, titles: &Arc<Mutex<Vec<String>>>| -> Box<dyn Notifier> {
+            Box::new(Recorder {
+                name,
+                fail: false,
+                incidents: false,
+                titles: titles.clone(),
+            })
+        };
+        let rule = |notifiers: &[&str]| RuleConfig {
+            name: "liquidation".to_string(),
+            when: Condition::LogMatch {
+                program: None,
+                regex: "Liquidate".to_string(),
+            },
+            notifiers: notifiers.iter().map(|name| name.to_string()).collect(),
+            cooldown_secs: None,
+            severity: Severity::Warning,
+        };
+        let matcher = || EventMatcher::new(vec![EventType::Log], None).unwrap();
+
+        let unknown = Notifications::new(vec![], matcher(), policy(), metrics.clone())
+            .with_rules(RuleSet::new(vec![rule(&["email"])]).unwrap());
+        assert!(unknown.is_err());
+
+        let notifications = Notifications::new(
+            vec![recorder("slack", &slack), recorder("pagerduty", &pagerduty)],
+            matcher(),
+            policy(),
+            metrics.clone(),
+        )
+        .with_rules(RuleSet::new(vec![rule(&["pagerduty"])]).unwrap())
+        .unwrap();
+        let liquidation = Envelope::test(LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "sig".to_string(),
+            1,
+            "prog".to_string(),
+            vec!["Program log: Instruction: Liquidate".to_string()],
+        ));
+        let deposit = Envelope::test(LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "sig".to_string(),
+            1,
+            "prog".to_string(),
+            vec!["Program log: Instruction: Deposit".to_string()],
+        ));
+        notifications.offer(&liquidation);
+        notifications.offer(&deposit);
+        notifications.close(Duration::from_secs(5)).await;
+
+        assert!(slack.lock().unwrap().is_empty());
+        assert_eq!(
+            *pagerduty.lock().unwrap(),
+            vec!["liquidation: log matched: Program log: Instruction: Liquidate"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_burst_is_grouped() {
+        let metrics = MetricsRegistry::default();
+        let titles = Arc::new(Mutex::new(Vec::new()));
+        let incidents = Arc::new(Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(Recorder {
+                name: "slack",
+                fail: false,
+                incidents: false,
+                titles: titles.clone(),
+            }),
+            Box::new(Recorder {
+                name: "pagerduty",
+                fail: false,
+                incidents: true,
+                titles: incidents.clone(),
+            }),
+        ];
+        let matcher = EventMatcher::new(vec![EventType::Log], Some("Liquidate")).unwrap();
+        let notifications = Notifications::new(notifiers, matcher, policy(), metrics.clone());
+
+        for slot in 0..500 {
+            notifications.offer(&Envelope::test(LogEvent::new(
+                "2024-01-15T10:30:45Z".to_string(),
+                format!("sig{}", slot),
+                slot,
+                "prog".to_string(),
+                vec!["Program log: Instruction: Liquidate".to_string()],
+            )));
+        }
+        notifications.close(Duration::from_secs(5)).await;
+
+        assert_eq!(
+            *titles.lock().unwrap(),
+            vec![
+                "Transaction sig0 in slot 0",
+                "log: 499 more alerts in the last 60s"
+            ]
+        );
+        // Keyless summaries would open incidents that never resolve
+        assert_eq!(*incidents.lock().unwrap(), vec!["Transaction sig0 in slot 0"]);
+        let count = |result: &str| metrics.alerts_total.with_label_values(&[result]).get();
+        assert_eq!(count("sent"), 1.0);
+        assert_eq!(count("grouped"), 499.0);
+        assert_eq!(count("summary"), 1.0);
+    }
+
+    /// Batches alerts until flushed, recording batch sizes
+    struct Batcher {
+        pending: Mutex<usize>,
+        batches: Arc<Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait]
+    impl Notifier for Batcher {
+        fn name(&self) -> &str {
+            "batcher"
+        }
+
+        async fn notify(&self, _alert: &Alert) -> Result<()> {
+            *self.pending.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        fn flush_interval(&self) -> Option<Duration> {
+            Some(Duration::from_secs(3600))
+        }
+
+        async fn flush(&self) -> Result<usize> {
+            let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+            if pending > 0 {
+                self.batches.lock().unwrap().push(pending);
+            }
+            Ok(pending)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_flushed_on_close() {
+        let metrics = MetricsRegistry::default();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(Batcher {
+            pending: Mutex::new(0),
+            batches: batches.clone(),
+        })];
+        let matcher = EventMatcher::new(vec![EventType::Log], None).unwrap();
+        let notifications = Notifications::new(notifiers, matcher, policy(), metrics.clone());
+
+        notifications.send(Alert::new(Severity::Info, "first"));
+        notifications.send(Alert::new(Severity::Info, "second"));
+        notifications.close(Duration::from_secs(5)).await;
+
+        assert_eq!(*batches.lock().unwrap(), vec![2]);
+        let count = |result: &str| {
+            metrics
+                .notifications_total
+                .with_label_values(&["batcher", result])
+                .get()
+        };
+        assert_eq!(count("batched"), 2.0);
+        assert_eq!(count("sent"), 1.0);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("liquidation", 6), "liqui…");
+        assert_eq!(truncate("ééééé", 3), "éé…");
+    }
+
+    #[test]
+    fn test_event_matcher() {
+        let log = Envelope::test(LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "sig".to_string(),
+            1,
+            "prog".to_string(),
+            vec!["Program log: Instruction: Liquidate".to_string()],
+        ));
+        let account = Envelope::test(AccountEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "pubkey".to_string(),
+            1,
+            0,
+            String::new(),
+        ));
+
+        let matcher = EventMatcher::new(vec![EventType::Log], Some("Liquidate")).unwrap();
+        assert!(matcher.matches(&log));
+        assert!(!matcher.matches(&account));
+
+        let everything = EventMatcher::new(vec![EventType::Log, EventType::Account], None).unwrap();
+        assert!(everything.matches(&account));
+    }
+}
@@ -0,0 +1,148 @@
+//! Discord webhook notifier
+
+use super::text::{render_messages, Markup};
+use super::{Explorer, JsonPoster, Notifier};
+use crate::alert::Alert;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+/// Longest message content Discord accepts
+const MAX_MESSAGE_CHARS: usize = 2000;
+
+/// Notifier posting Markdown messages to a Discord webhook
+///
+/// Alerts longer than Discord's limit are sent as several messages. Mentions
+/// are disabled so event data can never ping `@everyone`.
+pub struct DiscordNotifier {
+    poster: JsonPoster,
+    url: String,
+    explorer: Explorer,
+}
+
+impl DiscordNotifier {
+    pub fn new(poster: JsonPoster, url: String, explorer: Explorer) -> Self {
+        Self {
+            poster,
+            url,
+            explorer,
+        }
+    }
+
+    /// Message contents for an alert, each within Discord's limit
+    fn messages(&self, alert: &Alert) -> Vec<String> {
+        render_messages(alert, &self.explorer, &DiscordMarkdown, MAX_MESSAGE_CHARS)
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        for content in self.messages(alert) {
+            let body = json!({
+                "content": content,
+                "allowed_mentions": {"parse": []},
+            });
+            let body = serde_json::to_vec(&body).context("Failed to serialize message")?;
+            self.poster.post(&self.url, &body, HeaderMap::new()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Discord's Markdown dialect
+struct DiscordMarkdown;
+
+impl Markup for DiscordMarkdown {
+    fn escape(&self, text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '[' | ']' | '(' | ')') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
+    fn bold(&self, text: &str) -> String {
+        format!("**{}**", text)
+    }
+
+    fn code(&self, text: &str) -> String {
+        // Backticks cannot be escaped inside inline code
+        format!("`{}`", text.replace('`', "'"))
+    }
+
+    fn link(&self, url: &str, text: &str) -> String {
+        format!("[{}](<{}>)", self.escape(text), url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::Severity;
+    use crate::event::{Envelope, LogEvent};
+    use std::time::Duration;
+
+    fn notifier() -> DiscordNotifier {
+        DiscordNotifier::new(
+            JsonPoster::new(Duration::from_secs(1), 0).unwrap(),
+            "http://localhost".to_string(),
+            Explorer::new(
+                "https://explorer.solana.com/tx/{signature}?cluster={cluster}",
+                "https://explorer.solana.com/address/{address}?cluster={cluster}",
+                "mainnet-beta",
+            ),
+        )
+    }
+
+    #[test]
+    fn test_discord_message() {
+        let envelope = Envelope::test(LogEvent {
+            decoded: [(
+                "prog".to_string(),
+                json!({"event": "Liquidation_Event", "amount": 5000}),
+            )]
+            .into(),
+            ..LogEvent::new(
+                "2024-01-15T10:30:45Z".to_string(),
+                "sig".to_string(),
+                12345,
+                "prog".to_string(),
+                vec![],
+            )
+        });
+
+        let messages = notifier().messages(&Alert::from_envelope(&envelope));
+        assert_eq!(messages.len(), 1);
+        let lines: Vec<&str> = messages[0].lines().collect();
+        assert_eq!(lines[0], "ℹ️ **Transaction sig in slot 12345**");
+        assert_eq!(
+            lines[1],
+            "**Signature:** [sig](<https://explorer.solana.com/tx/sig?cluster=mainnet-beta>)"
+        );
+        assert_eq!(lines[2], "**Slot:** 12345");
+        assert_eq!(lines[4], "**Event:** Liquidation\\_Event");
+        assert_eq!(lines[5], "**amount:** `5000`");
+    }
+
+    #[test]
+    fn test_long_alert_is_split() {
+        let alert = Alert {
+            body: vec!["x".repeat(100); 50].join("\n"),
+            ..Alert::new(Severity::Critical, "Listener disconnected")
+        };
+
+        let messages = notifier().messages(&alert);
+        assert_eq!(messages.len(), 3);
+        assert!(messages.iter().all(|message| message.chars().count() <= MAX_MESSAGE_CHARS));
+        assert!(messages[0].starts_with("🚨 **Listener disconnected**"));
+    }
+}
@@ -0,0 +1,336 @@
+//! SMTP email notifier
+
+use super::text::{render, Markup};
+use super::{Explorer, Notifier};
+use crate::alert::{Alert, Severity};
+use crate::config::SmtpTls;
+use crate::event::format_timestamp;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::sync::Mutex;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// Most alerts listed in one digest; the rest are only counted
+const MAX_DIGEST_ALERTS: usize = 1000;
+
+/// Email notifier settings
+#[derive(Debug, Clone)]
+pub struct EmailOptions {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Prepended to every subject
+    pub subject_prefix: String,
+    pub timeout: Duration,
+    /// Batch alerts into one email per window instead of one per alert
+    pub digest_window: Option<Duration>,
+}
+
+/// Alerts waiting for the next digest
+#[derive(Default)]
+struct Batch {
+    alerts: Vec<Alert>,
+    /// Alerts beyond [`MAX_DIGEST_ALERTS`]
+    omitted: usize,
+}
+
+/// Notifier emailing alerts through an SMTP server
+///
+/// Sends one plain-text email per alert, or in digest mode one email per
+/// window listing every alert raised in it. A digest that fails to send is
+/// kept and retried with the next one.
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    subject_prefix: String,
+    explorer: Explorer,
+    digest_window: Option<Duration>,
+    batch: Mutex<Batch>,
+}
+
+impl EmailNotifier {
+    pub fn new(options: EmailOptions, explorer: Explorer) -> Result<Self> {
+        let mut builder = match options.tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&options.host)
+                .with_context(|| format!("Invalid SMTP host: {}", options.host))?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&options.host)
+                .with_context(|| format!("Invalid SMTP host: {}", options.host))?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&options.host),
+        };
+        builder = builder.port(options.port).timeout(Some(options.timeout));
+        if let Some(username) = options.username.filter(|username| !username.is_empty()) {
+            builder = builder.credentials(Credentials::new(
+                username,
+                options.password.unwrap_or_default(),
+            ));
+        }
+
+        let from = options
+            .from
+            .parse()
+            .with_context(|| format!("Invalid sender address: {}", options.from))?;
+        let to = options
+            .to
+            .iter()
+            .map(|to| to.trim())
+            .filter(|to| !to.is_empty())
+            .map(|to| to.parse().with_context(|| format!("Invalid recipient address: {}", to)))
+            .collect::<Result<Vec<Mailbox>>>()?;
+        anyhow::ensure!(!to.is_empty(), "Email notifier needs at least one recipient");
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+            to,
+            subject_prefix: options.subject_prefix,
+            explorer,
+            digest_window: options.digest_window,
+            batch: Mutex::new(Batch::default()),
+        })
+    }
+
+    async fn send(&self, subject: &str, body: String) -> Result<()> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(format!("{}{}", self.subject_prefix, subject))
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message.body(body).context("Failed to build email")?;
+
+        self.transport
+            .send(message)
+            .await
+            .context("SMTP server rejected email")?;
+        Ok(())
+    }
+
+    /// Subject and body of a digest
+    fn digest(&self, batch: &Batch) -> (String, String) {
+        let total = batch.alerts.len() + batch.omitted;
+        let count = |severity| batch.alerts.iter().filter(|alert| alert.severity == severity).count();
+        let since = batch.alerts.first().map(|alert| alert.timestamp.as_str()).unwrap_or_default();
+        let until = format_timestamp(OffsetDateTime::now_utc()).unwrap_or_default();
+
+        let mut body = format!(
+            "{} alerts from {} to {}: {} critical, {} warning, {} info\n",
+            total,
+            since,
+            until,
+            count(Severity::Critical),
+            count(Severity::Warning),
+            count(Severity::Info)
+        );
+        for alert in &batch.alerts {
+            body.push('\n');
+            body.push_str(&render(alert, &self.explorer, &PlainText));
+            body.push('\n');
+        }
+        if batch.omitted > 0 {
+            body.push_str(&format!("\n…and {} more\n", batch.omitted));
+        }
+        (format!("Digest: {} alerts since {}", total, since), body)
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        if self.digest_window.is_some() {
+            let mut batch = self.batch.lock().unwrap();
+            if batch.alerts.len() < MAX_DIGEST_ALERTS {
+                batch.alerts.push(alert.clone());
+            } else {
+                batch.omitted += 1;
+            }
+            return Ok(());
+        }
+        self.send(&alert.title, render(alert, &self.explorer, &PlainText)).await
+    }
+
+    fn flush_interval(&self) -> Option<Duration> {
+        self.digest_window
+    }
+
+    async fn flush(&self) -> Result<usize> {
+        let batch = std::mem::take(&mut *self.batch.lock().unwrap());
+        if batch.alerts.is_empty() {
+            return Ok(0);
+        }
+
+        let (subject, body) = self.digest(&batch);
+        match self.send(&subject, body).await {
+            Ok(()) => Ok(batch.alerts.len() + batch.omitted),
+            Err(e) => {
+                // Put the alerts back ahead of any raised meanwhile
+                let mut pending = self.batch.lock().unwrap();
+                let newer = std::mem::take(&mut *pending);
+                *pending = batch;
+                for alert in newer.alerts {
+                    if pending.alerts.len() < MAX_DIGEST_ALERTS {
+                        pending.alerts.push(alert);
+                    } else {
+                        pending.omitted += 1;
+                    }
+                }
+                pending.omitted += newer.omitted;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Plain text with links spelled out
+struct PlainText;
+
+impl Markup for PlainText {
+    fn escape(&self, text: &str) -> String {
+        text.to_string()
+    }
+
+    fn bold(&self, text: &str) -> String {
+        text.to_string()
+    }
+
+    fn code(&self, text: &str) -> String {
+        text.to_string()
+    }
+
+    fn link(&self, url: &str, text: &str) -> String {
+        format!("{} <{}>", text, url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Minimal SMTP sink recording the DATA of every message
+    async fn spawn_smtp_sink(messages: Arc<Mutex<Vec<String>>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let messages = messages.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = socket.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let reply: &[u8] = match line.to_ascii_uppercase().as_str() {
+                            "DATA" => {
+                                writer.write_all(b"354 go ahead\r\n").await.unwrap();
+                                let mut data = Vec::new();
+                                while let Ok(Some(line)) = lines.next_line().await {
+                                    if line == "." {
+                                        break;
+                                    }
+                                    data.push(line);
+                                }
+                                messages.lock().unwrap().push(data.join("\n"));
+                                b"250 queued\r\n"
+                            }
+                            "QUIT" => {
+                                writer.write_all(b"221 bye\r\n").await.unwrap();
+                                return;
+                            }
+                            _ => b"250 ok\r\n",
+                        };
+                        writer.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    fn notifier(port: u16, digest_window: Option<Duration>) -> EmailNotifier {
+        EmailNotifier::new(
+            EmailOptions {
+                host: "127.0.0.1".to_string(),
+                port,
+                tls: SmtpTls::None,
+                username: None,
+                password: None,
+                from: "Listener <listener@example.com>".to_string(),
+                to: vec!["compliance@example.com".to_string()],
+                subject_prefix: "[sol] ".to_string(),
+                timeout: Duration::from_secs(5),
+                digest_window,
+            },
+            Explorer::new("https://x.io/tx/{signature}", "https://x.io/address/{address}", "devnet"),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_immediate_email() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let port = spawn_smtp_sink(messages.clone()).await;
+        let notifier = notifier(port, None);
+
+        notifier.notify(&Alert::new(Severity::Critical, "Listener disconnected")).await.unwrap();
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("Subject: [sol] Listener disconnected"));
+        assert!(messages[0].contains("To: compliance@example.com"));
+        assert!(messages[0].contains("critical"));
+    }
+
+    #[tokio::test]
+    async fn test_digest_batches_alerts() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let port = spawn_smtp_sink(messages.clone()).await;
+        let notifier = notifier(port, Some(Duration::from_secs(86_400)));
+        assert_eq!(notifier.flush_interval(), Some(Duration::from_secs(86_400)));
+
+        notifier.notify(&Alert::new(Severity::Info, "Treasury balance changed")).await.unwrap();
+        notifier.notify(&Alert::new(Severity::Warning, "Treasury owner changed")).await.unwrap();
+        assert!(messages.lock().unwrap().is_empty());
+
+        assert_eq!(notifier.flush().await.unwrap(), 2);
+        assert_eq!(notifier.flush().await.unwrap(), 0);
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("Subject: [sol] Digest: 2 alerts since"));
+        assert!(messages[0].contains("Treasury balance changed"));
+        assert!(messages[0].contains("Treasury owner changed"));
+        assert!(messages[0].contains("0 critical, 1 warning, 1 info"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_digest_is_kept() {
+        // Nothing listens on the port, so sending fails
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let notifier = notifier(port, Some(Duration::from_secs(60)));
+
+        notifier.notify(&Alert::new(Severity::Info, "first")).await.unwrap();
+        assert!(notifier.flush().await.is_err());
+        notifier.notify(&Alert::new(Severity::Info, "second")).await.unwrap();
+
+        let batch = notifier.batch.lock().unwrap();
+        let titles: Vec<&str> = batch.alerts.iter().map(|alert| alert.title.as_str()).collect();
+        assert_eq!(titles, vec!["first", "second"]);
+    }
+}
@@ -0,0 +1,53 @@
+//! Block explorer links for chat notifiers
+
+/// Default transaction link; `{cluster}` is replaced by `CLUSTER`
+pub const DEFAULT_TX_URL: &str = "https://explorer.solana.com/tx/{signature}?cluster={cluster}";
+
+/// Default account link; `{cluster}` is replaced by `CLUSTER`
+pub const DEFAULT_ADDRESS_URL: &str =
+    "https://explorer.solana.com/address/{address}?cluster={cluster}";
+
+/// Explorer URL templates with the cluster filled in
+#[derive(Debug, Clone)]
+pub struct Explorer {
+    tx_url: String,
+    address_url: String,
+}
+
+impl Explorer {
+    pub fn new(tx_url: &str, address_url: &str, cluster: &str) -> Self {
+        Self {
+            tx_url: tx_url.replace("{cluster}", cluster),
+            address_url: address_url.replace("{cluster}", cluster),
+        }
+    }
+
+    /// Link to a transaction
+    pub fn tx(&self, signature: &str) -> String {
+        self.tx_url.replace("{signature}", signature)
+    }
+
+    /// Link to an account or program
+    pub fn address(&self, address: &str) -> String {
+        self.address_url.replace("{address}", address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explorer_links() {
+        let explorer = Explorer::new(
+            "https://solscan.io/tx/{signature}?cluster={cluster}",
+            DEFAULT_ADDRESS_URL,
+            "devnet",
+        );
+        assert_eq!(explorer.tx("sig"), "https://solscan.io/tx/sig?cluster=devnet");
+        assert_eq!(
+            explorer.address("prog"),
+            "https://explorer.solana.com/address/prog?cluster=devnet"
+        );
+    }
+}
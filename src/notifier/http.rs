@@ -0,0 +1,143 @@
+//! JSON POSTs with retries shared by the HTTP notifiers
+
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::time::Duration;
+use tracing::debug;
+
+/// Delay before the first retry; doubled for each further attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest `Retry-After` honored before retrying
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Outcome of a single attempt
+enum Attempt {
+    Delivered,
+    /// Retryable failure, with the delay the receiver asked for
+    Retry(Option<Duration>),
+}
+
+/// HTTP client POSTing JSON bodies with retries
+///
+/// Connection errors, timeouts, `429` and `5xx` responses are retried with
+/// exponential backoff, or after the receiver's `Retry-After`; other `4xx`
+/// responses are not.
+#[derive(Clone)]
+pub struct JsonPoster {
+    client: reqwest::Client,
+    max_retries: u32,
+}
+
+impl JsonPoster {
+    pub fn new(timeout: Duration, max_retries: u32) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self {
+            client,
+            max_retries,
+        })
+    }
+
+    /// POST `body` to `url` until it is accepted or retries run out
+    pub async fn post(&self, url: &str, body: &[u8], headers: HeaderMap) -> Result<()> {
+        let mut delay = INITIAL_RETRY_DELAY;
+        for attempt in 0..=self.max_retries {
+            match self.send(url, body, headers.clone()).await? {
+                Attempt::Delivered => return Ok(()),
+                Attempt::Retry(_) if attempt == self.max_retries => {}
+                Attempt::Retry(retry_after) => {
+                    tokio::time::sleep(retry_after.unwrap_or(delay).min(MAX_RETRY_AFTER)).await;
+                    delay *= 2;
+                }
+            }
+        }
+        anyhow::bail!("Request failed after {} attempts", self.max_retries + 1)
+    }
+
+    async fn send(&self, url: &str, body: &[u8], headers: HeaderMap) -> Result<Attempt> {
+        let request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .headers(headers)
+            .body(body.to_vec());
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                // The URL may hold a bot token
+                debug!("Request failed: {}", e.without_url());
+                return Ok(Attempt::Retry(None));
+            }
+        };
+        let status = response.status();
+        if status.is_success() {
+            Ok(Attempt::Delivered)
+        } else if status == StatusCode::TOO_MANY_REQUESTS {
+            debug!("Receiver is rate limiting requests");
+            let header = retry_after(response.headers());
+            let body = response.json::<serde_json::Value>().await.ok();
+            Ok(Attempt::Retry(header.or_else(|| body.as_ref().and_then(retry_after_body))))
+        } else if status.is_server_error() {
+            debug!("Receiver returned {}", status);
+            Ok(Attempt::Retry(None))
+        } else {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Receiver rejected notification with {}: {}", status, text.trim())
+        }
+    }
+}
+
+/// Delay from a `Retry-After: <seconds>` header
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// Delay from a rate-limit response body
+///
+/// Discord sends `{"retry_after": <seconds>}`, Telegram
+/// `{"parameters": {"retry_after": <seconds>}}`.
+fn retry_after_body(body: &serde_json::Value) -> Option<Duration> {
+    body.get("retry_after")
+        .or_else(|| body.pointer("/parameters/retry_after"))?
+        .as_f64()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("2"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_retry_after_body() {
+        let discord = serde_json::json!({"message": "You are being rate limited.", "retry_after": 0.5, "global": false});
+        assert_eq!(retry_after_body(&discord), Some(Duration::from_millis(500)));
+        let telegram = serde_json::json!({"ok": false, "error_code": 429, "parameters": {"retry_after": 3}});
+        assert_eq!(retry_after_body(&telegram), Some(Duration::from_secs(3)));
+        assert_eq!(retry_after_body(&serde_json::json!({"ok": false})), None);
+    }
+}
@@ -0,0 +1,151 @@
+//! Opsgenie Alert API notifier
+
+use super::{details_json, truncate, JsonPoster, Notifier};
+use crate::alert::{Alert, Severity};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Url;
+use serde_json::{json, Value};
+
+/// Default API base URL; EU accounts use `https://api.eu.opsgenie.com`
+pub const DEFAULT_OPSGENIE_URL: &str = "https://api.opsgenie.com";
+
+/// Longest message Opsgenie accepts
+const MAX_MESSAGE_CHARS: usize = 130;
+
+/// Longest description Opsgenie accepts
+const MAX_DESCRIPTION_CHARS: usize = 15_000;
+
+/// Notifier creating and closing Opsgenie alerts
+///
+/// The dedup key becomes the alert alias, so Opsgenie deduplicates repeats
+/// and a resolved alert closes the one it opened.
+pub struct OpsgenieNotifier {
+    poster: JsonPoster,
+    base_url: Url,
+    headers: HeaderMap,
+    source: String,
+}
+
+impl OpsgenieNotifier {
+    pub fn new(poster: JsonPoster, base_url: &str, api_key: &str, source: String) -> Result<Self> {
+        let base_url =
+            Url::parse(base_url).with_context(|| format!("Invalid Opsgenie URL: {}", base_url))?;
+        let mut headers = HeaderMap::new();
+        let mut key = HeaderValue::from_str(&format!("GenieKey {}", api_key))
+            .context("Invalid Opsgenie API key")?;
+        key.set_sensitive(true);
+        headers.insert(AUTHORIZATION, key);
+
+        Ok(Self {
+            poster,
+            base_url,
+            headers,
+            source,
+        })
+    }
+
+    /// URL under the API base
+    fn url(&self, segments: &[&str]) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Opsgenie URL cannot be a base"))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    /// Request URL and body for an alert; `None` if there is nothing to do
+    fn request(&self, alert: &Alert) -> Result<Option<(Url, Value)>> {
+        if alert.resolved {
+            let Some(alias) = &alert.dedup_key else {
+                return Ok(None);
+            };
+            let mut url = self.url(&["v2", "alerts", alias, "close"])?;
+            url.query_pairs_mut().append_pair("identifierType", "alias");
+            let body = json!({"source": self.source, "note": alert.title});
+            return Ok(Some((url, body)));
+        }
+
+        let priority = match alert.severity {
+            Severity::Info => "P5",
+            Severity::Warning => "P3",
+            Severity::Critical => "P1",
+        };
+        let details: serde_json::Map<String, Value> = details_json(alert)
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::String(_) => (key, value),
+                other => (key, json!(other.to_string())),
+            })
+            .collect();
+        let mut body = json!({
+            "message": truncate(&alert.title, MAX_MESSAGE_CHARS),
+            "description": truncate(&format!("{}\n{}", alert.title, alert.body), MAX_DESCRIPTION_CHARS),
+            "priority": priority,
+            "source": self.source,
+            "details": details,
+        });
+        if let Some(alias) = &alert.dedup_key {
+            body["alias"] = json!(alias);
+        }
+        Ok(Some((self.url(&["v2", "alerts"])?, body)))
+    }
+}
+
+#[async_trait]
+impl Notifier for OpsgenieNotifier {
+    fn name(&self) -> &str {
+        "opsgenie"
+    }
+
+    fn tracks_incidents(&self) -> bool {
+        true
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let Some((url, body)) = self.request(alert)? else {
+            return Ok(());
+        };
+        let body = serde_json::to_vec(&body).context("Failed to serialize alert")?;
+        self.poster.post(url.as_str(), &body, self.headers.clone()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_opsgenie_requests() {
+        let notifier = OpsgenieNotifier::new(
+            JsonPoster::new(Duration::from_secs(1), 0).unwrap(),
+            "https://api.eu.opsgenie.com/",
+            "key",
+            "mainnet".to_string(),
+        )
+        .unwrap();
+        let alert = Alert {
+            dedup_key: Some("solana-event-listener/mainnet/stalled_slot".to_string()),
+            ..Alert::new(Severity::Critical, "Slot has not advanced")
+        };
+
+        let (url, body) = notifier.request(&alert).unwrap().unwrap();
+        assert_eq!(url.as_str(), "https://api.eu.opsgenie.com/v2/alerts");
+        assert_eq!(body["priority"], "P1");
+        assert_eq!(body["alias"], "solana-event-listener/mainnet/stalled_slot");
+
+        let resolved = Alert {
+            resolved: true,
+            ..alert
+        };
+        let (url, body) = notifier.request(&resolved).unwrap().unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://api.eu.opsgenie.com/v2/alerts/solana-event-listener%2Fmainnet%2Fstalled_slot/close?identifierType=alias"
+        );
+        assert_eq!(body["source"], "mainnet");
+    }
+}
@@ -0,0 +1,133 @@
+//! PagerDuty Events API v2 notifier
+
+use super::{details_json, truncate, JsonPoster, Notifier};
+use crate::alert::{Alert, Severity};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde_json::{json, Value};
+
+/// Default Events API v2 endpoint
+pub const DEFAULT_PAGERDUTY_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Longest summary PagerDuty accepts
+const MAX_SUMMARY_CHARS: usize = 1024;
+
+/// Notifier triggering and resolving PagerDuty incidents
+///
+/// Alerts with a dedup key trigger (or, once resolved, resolve) the incident
+/// for that key; other alerts trigger an incident of their own.
+pub struct PagerDutyNotifier {
+    poster: JsonPoster,
+    url: String,
+    routing_key: String,
+    /// `payload.source`, e.g. the listener instance
+    source: String,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(poster: JsonPoster, url: String, routing_key: String, source: String) -> Self {
+        Self {
+            poster,
+            url,
+            routing_key,
+            source,
+        }
+    }
+
+    /// Events API v2 event for an alert; `None` if there is nothing to do
+    fn event(&self, alert: &Alert) -> Option<Value> {
+        if alert.resolved {
+            let dedup_key = alert.dedup_key.as_ref()?;
+            return Some(json!({
+                "routing_key": self.routing_key,
+                "event_action": "resolve",
+                "dedup_key": dedup_key,
+            }));
+        }
+
+        let severity = match alert.severity {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        };
+        let mut event = json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "payload": {
+                "summary": truncate(&alert.title, MAX_SUMMARY_CHARS),
+                "source": self.source,
+                "severity": severity,
+                "timestamp": alert.timestamp,
+                "custom_details": details_json(alert),
+            },
+        });
+        if let Some(dedup_key) = &alert.dedup_key {
+            event["dedup_key"] = json!(dedup_key);
+        }
+        Some(event)
+    }
+}
+
+#[async_trait]
+impl Notifier for PagerDutyNotifier {
+    fn name(&self) -> &str {
+        "pagerduty"
+    }
+
+    fn tracks_incidents(&self) -> bool {
+        true
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let Some(event) = self.event(alert) else {
+            return Ok(());
+        };
+        let body = serde_json::to_vec(&event).context("Failed to serialize event")?;
+        self.poster.post(&self.url, &body, HeaderMap::new()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pagerduty_events() {
+        let notifier = PagerDutyNotifier::new(
+            JsonPoster::new(Duration::from_secs(1), 0).unwrap(),
+            DEFAULT_PAGERDUTY_URL.to_string(),
+            "key".to_string(),
+            "mainnet".to_string(),
+        );
+        let trigger = Alert {
+            dedup_key: Some("listener/disconnected".to_string()),
+            ..Alert::new(Severity::Critical, "WebSocket disconnected")
+        };
+
+        let event = notifier.event(&trigger).unwrap();
+        assert_eq!(event["event_action"], "trigger");
+        assert_eq!(event["dedup_key"], "listener/disconnected");
+        assert_eq!(event["payload"]["severity"], "critical");
+        assert_eq!(event["payload"]["source"], "mainnet");
+
+        let resolve = notifier
+            .event(&Alert {
+                resolved: true,
+                ..trigger
+            })
+            .unwrap();
+        assert_eq!(
+            resolve,
+            json!({"routing_key": "key", "event_action": "resolve", "dedup_key": "listener/disconnected"})
+        );
+
+        // Nothing to resolve without a key
+        let resolved = Alert {
+            resolved: true,
+            ..Alert::new(Severity::Info, "done")
+        };
+        assert!(notifier.event(&resolved).is_none());
+    }
+}
@@ -0,0 +1,201 @@
+//! Slack incoming-webhook notifier
+
+use super::text::{shorten, status};
+use super::{truncate, Explorer, JsonPoster, Notifier};
+use crate::alert::{Alert, Severity};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde_json::{json, Value};
+
+/// Longest header block text Slack accepts
+const MAX_HEADER_CHARS: usize = 150;
+
+/// Most fields Slack renders in one section block
+const MAX_FIELDS: usize = 10;
+
+/// Longest text Slack accepts in a section field
+const MAX_FIELD_CHARS: usize = 2000;
+
+/// Longest text Slack accepts in a section block
+const MAX_SECTION_CHARS: usize = 3000;
+
+/// Notifier posting Block Kit messages to a Slack incoming webhook
+///
+/// Messages show the signature and program linked to the block explorer, the
+/// slot, and the decoded event name and fields when a plugin decoded them.
+pub struct SlackNotifier {
+    poster: JsonPoster,
+    url: String,
+    explorer: Explorer,
+}
+
+impl SlackNotifier {
+    pub fn new(poster: JsonPoster, url: String, explorer: Explorer) -> Self {
+        Self {
+            poster,
+            url,
+            explorer,
+        }
+    }
+
+    /// Block Kit payload for an alert
+    fn message(&self, alert: &Alert) -> Value {
+        let details = alert.details();
+
+        let mut fields = Vec::new();
+        if let Some(signature) = &details.signature {
+            fields.push(("Signature", link(&self.explorer.tx(signature), &shorten(signature))));
+        }
+        if let Some(slot) = details.slot {
+            fields.push(("Slot", slot.to_string()));
+        }
+        if let Some(program) = &details.program {
+            fields.push(("Program", link(&self.explorer.address(program), &shorten(program))));
+        }
+        if let Some(account) = &details.account {
+            fields.push(("Account", link(&self.explorer.address(account), &shorten(account))));
+        }
+        if let Some(name) = &details.event_name {
+            fields.push(("Event", escape(name)));
+        }
+        let mut fields: Vec<Value> = fields
+            .into_iter()
+            .map(|(label, value)| field(label, &value))
+            .collect();
+        for (key, value) in &details.fields {
+            fields.push(field(&escape(key), &format!("`{}`", escape(value))));
+        }
+        fields.truncate(MAX_FIELDS);
+
+        let mut blocks = vec![json!({
+            "type": "header",
+            "text": {
+                "type": "plain_text",
+                "text": truncate(&alert.title, MAX_HEADER_CHARS),
+            },
+        })];
+        if !fields.is_empty() {
+            blocks.push(json!({"type": "section", "fields": fields}));
+        }
+        if !alert.body.is_empty() {
+            blocks.push(json!({
+                "type": "section",
+                "text": {"type": "mrkdwn", "text": truncate(&escape(&alert.body), MAX_SECTION_CHARS)},
+            }));
+        }
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{
+                "type": "mrkdwn",
+                "text": format!("{} *{}* · {}", emoji(alert), status(alert), alert.timestamp),
+            }],
+        }));
+
+        json!({
+            "text": format!("{} {}", emoji(alert), escape(&alert.title)),
+            "blocks": blocks,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let body = serde_json::to_vec(&self.message(alert)).context("Failed to serialize message")?;
+        self.poster.post(&self.url, &body, HeaderMap::new()).await
+    }
+}
+
+fn emoji(alert: &Alert) -> &'static str {
+    if alert.resolved {
+        return ":white_check_mark:";
+    }
+    match alert.severity {
+        Severity::Info => ":information_source:",
+        Severity::Warning => ":warning:",
+        Severity::Critical => ":rotating_light:",
+    }
+}
+
+/// Section field with a bold label above the value
+fn field(label: &str, value: &str) -> Value {
+    json!({
+        "type": "mrkdwn",
+        "text": truncate(&format!("*{}*\n{}", label, value), MAX_FIELD_CHARS),
+    })
+}
+
+fn link(url: &str, text: &str) -> String {
+    format!("<{}|{}>", url, escape(text))
+}
+
+/// Escape the characters Slack treats as markup
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Envelope, LogEvent};
+    use std::time::Duration;
+
+    #[test]
+    fn test_slack_message() {
+        let signature = "5VeKqzxb3GmzxTpqUumNYrwHv7cnZkfbH9z4ufRBxvyNK5dybmbwiKnHxyL6QG7bJKbqYXnhnTnF3F6kHFvM8Dz";
+        let program = "Lend1111111111111111111111111111111111111";
+        let envelope = Envelope::test(LogEvent {
+            decoded: [(
+                program.to_string(),
+                json!({"event": "LiquidationEvent", "amount": 5000, "note": "a<b"}),
+            )]
+            .into(),
+            ..LogEvent::new(
+                "2024-01-15T10:30:45Z".to_string(),
+                signature.to_string(),
+                12345,
+                program.to_string(),
+                vec![],
+            )
+        });
+        let notifier = SlackNotifier::new(
+            JsonPoster::new(Duration::from_secs(1), 0).unwrap(),
+            "http://localhost".to_string(),
+            Explorer::new(
+                "https://explorer.solana.com/tx/{signature}?cluster={cluster}",
+                "https://explorer.solana.com/address/{address}?cluster={cluster}",
+                "devnet",
+            ),
+        );
+
+        let message = notifier.message(&Alert::from_envelope(&envelope));
+        let blocks = message["blocks"].as_array().unwrap();
+        assert_eq!(blocks[0]["type"], "header");
+        let fields: Vec<&str> = blocks[1]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            fields[0],
+            format!(
+                "*Signature*\n<https://explorer.solana.com/tx/{}?cluster=devnet|5VeKqzxb…kHFvM8Dz>",
+                signature
+            )
+        );
+        assert_eq!(fields[1], "*Slot*\n12345");
+        assert!(fields[2].starts_with("*Program*\n<https://explorer.solana.com/address/Lend"));
+        assert_eq!(fields[3], "*Event*\nLiquidationEvent");
+        assert_eq!(fields[4], "*amount*\n`5000`");
+        assert_eq!(fields[5], "*note*\n`a&lt;b`");
+        assert_eq!(blocks.last().unwrap()["type"], "context");
+    }
+}
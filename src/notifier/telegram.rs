@@ -0,0 +1,158 @@
+//! Telegram Bot API notifier
+
+use super::text::{render_messages, Markup};
+use super::{Explorer, JsonPoster, Notifier};
+use crate::alert::Alert;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+/// Longest message text Telegram accepts
+const MAX_MESSAGE_CHARS: usize = 4096;
+
+/// Notifier sending HTML messages to a Telegram chat through a bot
+///
+/// Alerts longer than Telegram's limit are sent as several messages.
+pub struct TelegramNotifier {
+    poster: JsonPoster,
+    /// `sendMessage` endpoint, including the bot token
+    url: String,
+    chat_id: String,
+    explorer: Explorer,
+}
+
+impl TelegramNotifier {
+    pub fn new(
+        poster: JsonPoster,
+        api_url: &str,
+        token: &str,
+        chat_id: String,
+        explorer: Explorer,
+    ) -> Self {
+        Self {
+            poster,
+            url: format!("{}/bot{}/sendMessage", api_url.trim_end_matches('/'), token),
+            chat_id,
+            explorer,
+        }
+    }
+
+    /// Message texts for an alert, each within Telegram's limit
+    fn messages(&self, alert: &Alert) -> Vec<String> {
+        render_messages(alert, &self.explorer, &TelegramHtml, MAX_MESSAGE_CHARS)
+    }
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        for text in self.messages(alert) {
+            let body = json!({
+                "chat_id": self.chat_id,
+                "text": text,
+                "parse_mode": "HTML",
+                "disable_web_page_preview": true,
+            });
+            let body = serde_json::to_vec(&body).context("Failed to serialize message")?;
+            self.poster.post(&self.url, &body, HeaderMap::new()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Telegram's HTML subset
+struct TelegramHtml;
+
+impl Markup for TelegramHtml {
+    fn escape(&self, text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    fn bold(&self, text: &str) -> String {
+        format!("<b>{}</b>", text)
+    }
+
+    fn code(&self, text: &str) -> String {
+        format!("<code>{}</code>", self.escape(text))
+    }
+
+    fn link(&self, url: &str, text: &str) -> String {
+        format!(
+            "<a href=\"{}\">{}</a>",
+            self.escape(url).replace('"', "&quot;"),
+            self.escape(text)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::Severity;
+    use axum::{extract::Path, http::StatusCode, routing::post, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_telegram_html() {
+        assert_eq!(TelegramHtml.escape("a<b & c>d"), "a&lt;b &amp; c&gt;d");
+        assert_eq!(
+            TelegramHtml.link("https://x.io/tx/a?b=1&c=\"2\"", "a"),
+            "<a href=\"https://x.io/tx/a?b=1&amp;c=&quot;2&quot;\">a</a>"
+        );
+    }
+
+    /// Bot API answering the first request with a 429 and `retry_after`
+    #[tokio::test]
+    async fn test_telegram_honours_retry_after() {
+        let texts = Arc::new(Mutex::new(Vec::new()));
+        let requests = Arc::new(AtomicUsize::new(0));
+        let received = texts.clone();
+        let app = Router::new().route(
+            "/:bot/sendMessage",
+            post(move |Path(bot): Path<String>, Json(body): Json<serde_json::Value>| {
+                let attempt = requests.fetch_add(1, Ordering::SeqCst);
+                assert_eq!(bot, "bottoken");
+                assert_eq!(body["chat_id"], "-100123");
+                received.lock().unwrap().push(body["text"].as_str().unwrap().to_string());
+                async move {
+                    if attempt == 0 {
+                        let limited = json!({"ok": false, "error_code": 429, "parameters": {"retry_after": 1}});
+                        (StatusCode::TOO_MANY_REQUESTS, Json(limited))
+                    } else {
+                        (StatusCode::OK, Json(json!({"ok": true})))
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let notifier = TelegramNotifier::new(
+            JsonPoster::new(Duration::from_secs(5), 1).unwrap(),
+            &format!("http://{}/", addr),
+            "token",
+            "-100123".to_string(),
+            Explorer::new("{signature}", "{address}", "devnet"),
+        );
+        let started = Instant::now();
+        notifier
+            .notify(&Alert::new(Severity::Warning, "Slot <stalled>"))
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        let texts = texts.lock().unwrap();
+        assert_eq!(texts.len(), 2);
+        assert!(texts[1].starts_with("⚠️ <b>Slot &lt;stalled&gt;</b>"));
+    }
+}
@@ -0,0 +1,257 @@
+//! Plain-text rendering of alerts for chat notifiers
+
+use super::Explorer;
+use crate::alert::{Alert, Severity};
+
+/// Formatting syntax of a chat platform
+pub trait Markup {
+    /// Escape characters the platform would treat as formatting
+    fn escape(&self, text: &str) -> String;
+    fn bold(&self, text: &str) -> String;
+    fn code(&self, text: &str) -> String;
+    fn link(&self, url: &str, text: &str) -> String;
+}
+
+/// Alert as lines of `markup`: title, body, event details, then severity
+pub fn render(alert: &Alert, explorer: &Explorer, markup: &impl Markup) -> String {
+    render_lines(alert, explorer, markup)
+        .into_iter()
+        .map(|line| line.marked)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Alert as messages of `markup` of at most `max` characters each
+///
+/// Lines are kept whole where possible. A line too long for one message is
+/// cut from its plain text and each piece escaped on its own, since cutting
+/// the formatted line could split a tag or entity; the pieces lose their
+/// formatting.
+pub fn render_messages(
+    alert: &Alert,
+    explorer: &Explorer,
+    markup: &impl Markup,
+    max: usize,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in render_lines(alert, explorer, markup) {
+        if line.marked.chars().count() <= max && !line.marked.contains('\n') {
+            lines.push(line.marked);
+        } else {
+            lines.extend(cut_plain(&line.plain, max, markup));
+        }
+    }
+    split(&lines.join("\n"), max)
+}
+
+/// One line of a rendered alert
+struct Line {
+    /// The line with `markup` applied
+    marked: String,
+    /// The same content without formatting, unescaped
+    plain: String,
+}
+
+fn render_lines(alert: &Alert, explorer: &Explorer, markup: &impl Markup) -> Vec<Line> {
+    let details = alert.details();
+    let mut lines = vec![Line {
+        marked: format!("{} {}", emoji(alert), markup.bold(&markup.escape(&alert.title))),
+        plain: format!("{} {}", emoji(alert), alert.title),
+    }];
+    lines.extend(alert.body.lines().map(|line| Line {
+        marked: markup.escape(line),
+        plain: line.to_string(),
+    }));
+
+    let mut field = |label: &str, value: String, plain: &str| {
+        lines.push(Line {
+            marked: format!("{} {}", markup.bold(&format!("{}:", markup.escape(label))), value),
+            plain: format!("{}: {}", label, plain),
+        });
+    };
+    if let Some(signature) = &details.signature {
+        let url = explorer.tx(signature);
+        field("Signature", markup.link(&url, &shorten(signature)), &url);
+    }
+    if let Some(slot) = details.slot {
+        field("Slot", slot.to_string(), &slot.to_string());
+    }
+    if let Some(program) = &details.program {
+        let url = explorer.address(program);
+        field("Program", markup.link(&url, &shorten(program)), &url);
+    }
+    if let Some(account) = &details.account {
+        let url = explorer.address(account);
+        field("Account", markup.link(&url, &shorten(account)), &url);
+    }
+    if let Some(name) = &details.event_name {
+        field("Event", markup.escape(name), name);
+    }
+    for (key, value) in &details.fields {
+        field(key, markup.code(value), value);
+    }
+
+    let footer = format!("{} · {}", status(alert), alert.timestamp);
+    lines.push(Line {
+        marked: markup.escape(&footer),
+        plain: footer,
+    });
+    lines
+}
+
+/// Cut plain text into escaped pieces of at most `max` characters each
+fn cut_plain(plain: &str, max: usize, markup: &impl Markup) -> Vec<String> {
+    let mut pieces = Vec::new();
+    for line in plain.lines() {
+        let mut piece = String::new();
+        let mut piece_len = 0;
+        for c in line.chars() {
+            let escaped = markup.escape(c.encode_utf8(&mut [0; 4]));
+            let len = escaped.chars().count();
+            if piece_len + len > max && piece_len > 0 {
+                pieces.push(std::mem::take(&mut piece));
+                piece_len = 0;
+            }
+            piece.push_str(&escaped);
+            piece_len += len;
+        }
+        pieces.push(piece);
+    }
+    pieces
+}
+
+fn emoji(alert: &Alert) -> &'static str {
+    if alert.resolved {
+        return "✅";
+    }
+    match alert.severity {
+        Severity::Info => "ℹ️",
+        Severity::Warning => "⚠️",
+        Severity::Critical => "🚨",
+    }
+}
+
+/// Severity, or `resolved` once the condition has cleared
+pub fn status(alert: &Alert) -> &'static str {
+    if alert.resolved {
+        "resolved"
+    } else {
+        alert.severity.as_str()
+    }
+}
+
+/// First and last characters of a long base58 string
+pub fn shorten(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 20 {
+        return value.to_string();
+    }
+    let head: String = chars[..8].iter().collect();
+    let tail: String = chars[chars.len() - 8..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+/// Split `text` into messages of at most `max` characters
+///
+/// Splits between lines where possible so formatting on a line stays
+/// intact; only a single line longer than `max` is cut mid-line, which is
+/// only safe for text without markup (see [`render_messages`]).
+pub fn split(text: &str, max: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+    for line in text.lines() {
+        let mut line: Vec<char> = line.chars().collect();
+        let separator = usize::from(current_len > 0);
+        if current_len + separator + line.len() > max && current_len > 0 {
+            messages.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        while line.len() > max {
+            messages.push(line.drain(..max).collect());
+        }
+        if current_len > 0 {
+            current.push('\n');
+            current_len += 1;
+        }
+        current.extend(line.iter());
+        current_len += line.len();
+    }
+    if current_len > 0 || messages.is_empty() {
+        messages.push(current);
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_between_lines() {
+        assert_eq!(split("short", 10), vec!["short"]);
+        assert_eq!(split("aaaa\nbbbb\ncccc", 9), vec!["aaaa\nbbbb", "cccc"]);
+        assert_eq!(split("aaaaaaaaaaaa\nbb", 5), vec!["aaaaa", "aaaaa", "aa\nbb"]);
+        for message in split(&"line of text\n".repeat(500), 2000) {
+            assert!(message.chars().count() <= 2000);
+        }
+    }
+
+    /// HTML-like markup whose tags and entities must never be cut
+    struct Html;
+
+    impl Markup for Html {
+        fn escape(&self, text: &str) -> String {
+            text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+        }
+
+        fn bold(&self, text: &str) -> String {
+            format!("<b>{}</b>", text)
+        }
+
+        fn code(&self, text: &str) -> String {
+            format!("<code>{}</code>", self.escape(text))
+        }
+
+        fn link(&self, url: &str, text: &str) -> String {
+            format!("<a href=\"{}\">{}</a>", url, text)
+        }
+    }
+
+    #[test]
+    fn test_long_lines_are_cut_without_markup() {
+        let alert = Alert {
+            body: "a&b<".repeat(30),
+            ..Alert::new(Severity::Warning, "Title ".repeat(10))
+        };
+        let explorer = Explorer::new(
+            "https://explorer.solana.com/tx/{signature}",
+            "https://explorer.solana.com/address/{address}",
+            "mainnet-beta",
+        );
+        let messages = render_messages(&alert, &explorer, &Html, 50);
+
+        for message in &messages {
+            assert!(message.chars().count() <= 50, "{}", message);
+            // Every entity is complete and no tag is left open
+            for (i, _) in message.match_indices('&') {
+                let rest = &message[i..];
+                assert!(rest.starts_with("&amp;") || rest.starts_with("&lt;"), "{}", message);
+            }
+            assert_eq!(message.matches("<b>").count(), message.matches("</b>").count());
+        }
+        let text = messages.join("\n");
+        assert_eq!(text.matches("&amp;").count(), 30);
+        assert_eq!(text.matches("&lt;").count(), 30);
+        assert!(text.contains("Title Title"));
+    }
+
+    #[test]
+    fn test_shorten() {
+        assert_eq!(shorten("short"), "short");
+        assert_eq!(
+            shorten("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+            "Tokenkeg…623VQ5DA"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Generic JSON webhook notifier
+
+use super::{JsonPoster, Notifier};
+use crate::alert::Alert;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderValue};
+use sha2::Sha256;
+
+/// Header carrying the hex HMAC-SHA256 of the request body
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// Notifier POSTing each alert as JSON to a URL
+///
+/// With a secret, each request carries
+/// `X-Signature-256: sha256=<hex HMAC-SHA256 of the body>`.
+pub struct WebhookNotifier {
+    poster: JsonPoster,
+    url: String,
+    secret: Option<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(poster: JsonPoster, url: String, secret: Option<String>) -> Self {
+        Self {
+            poster,
+            url,
+            secret: secret.filter(|secret| !secret.is_empty()),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let body = serde_json::to_vec(alert).context("Failed to serialize alert")?;
+        let mut headers = HeaderMap::new();
+        if let Some(secret) = &self.secret {
+            let signature = format!("sha256={}", sign(secret, &body));
+            headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&signature)?);
+        }
+        self.poster.post(&self.url, &body, headers).await
+    }
+}
+
+/// Hex HMAC-SHA256 of `body` keyed with `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::Severity;
+    use axum::{http::HeaderMap, http::StatusCode, routing::post, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    /// Signature header and body of each received request
+    type Requests = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+    /// Receiver answering `statuses` in turn (then 200), recording requests
+    async fn spawn_receiver(statuses: Vec<StatusCode>, signatures: Requests) -> String {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: axum::body::Bytes| {
+                let attempt = requests.fetch_add(1, Ordering::SeqCst);
+                let status = statuses.get(attempt).copied().unwrap_or(StatusCode::OK);
+                let signature = headers
+                    .get(SIGNATURE_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                signatures.lock().unwrap().push((signature, body.to_vec()));
+                async move { status }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/hook", addr)
+    }
+
+    fn notifier(url: String, secret: Option<&str>) -> WebhookNotifier {
+        let poster = JsonPoster::new(Duration::from_secs(5), 3).unwrap();
+        WebhookNotifier::new(poster, url, secret.map(String::from))
+    }
+
+    #[tokio::test]
+    async fn test_webhook_retries_and_signs() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let url = spawn_receiver(
+            vec![StatusCode::SERVICE_UNAVAILABLE, StatusCode::TOO_MANY_REQUESTS],
+            requests.clone(),
+        )
+        .await;
+
+        let alert = Alert::new(Severity::Info, "hello");
+        notifier(url, Some("secret")).notify(&alert).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        let (signature, body) = &requests[2];
+        assert_eq!(signature, &format!("sha256={}", sign("secret", body)));
+        let json: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(json["title"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_webhook_does_not_retry_client_errors() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let url = spawn_receiver(vec![StatusCode::BAD_REQUEST], requests.clone()).await;
+
+        let alert = Alert::new(Severity::Info, "hello");
+        assert!(notifier(url, None).notify(&alert).await.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert_eq!(requests.lock().unwrap()[0].0, "");
+    }
+}
@@ -0,0 +1,140 @@
+//! Processing shared by every subscription
+//!
+//! Decoded notifications go through block time resolution, plugin decoding
+//! and the script hook, then are wrapped in an envelope, stored, tracked
+//! for commitment changes, and offered to the notifiers.
+
+use crate::block_time::BlockTimeCache;
+use crate::commitment::CommitmentTracker;
+use crate::event::{Envelope, EnvelopeSource, Event};
+use crate::filter::{invoked_programs, LogFilter};
+use crate::metrics::MetricsRegistry;
+use crate::notifier::Notifications;
+use crate::plugin::PluginRegistry;
+use crate::script::{ScriptHook, ScriptOutcome};
+use crate::storage::SinkSet;
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// Everything needed to turn notifications into stored events
+pub struct Pipeline<'a> {
+    pub source: &'a EnvelopeSource,
+    pub block_times: Option<&'a BlockTimeCache>,
+    pub tracker: Option<&'a CommitmentTracker>,
+    /// Rules deciding which transactions are recorded in logs mode
+    pub log_filter: &'a LogFilter,
+    /// Script hook run on every log and account event
+    pub script: Option<&'a ScriptHook>,
+    /// WASM decoders keyed by program id
+    pub plugins: Option<&'a PluginRegistry>,
+    /// Alerts for matching events
+    pub notifications: &'a Notifications,
+    pub sinks: &'a SinkSet,
+    pub metrics: &'a MetricsRegistry,
+}
+
+impl Pipeline<'_> {
+    /// Resolve the block time of `slot` and record the notification latency
+    pub async fn block_time(&self, slot: u64, received_at: OffsetDateTime) -> Option<OffsetDateTime> {
+        let block_time = self.block_times?.block_time(slot).await?;
+        // Block times are truncated to the second, so small negative values are rounding
+        let latency = (received_at - block_time).as_seconds_f64().max(0.0);
+        self.metrics.notification_latency_seconds.observe(latency);
+        Some(block_time)
+    }
+
+    /// Decode log lines with the plugin of every invoked program
+    pub fn decode_logs(&self, logs: &[String]) -> BTreeMap<String, serde_json::Value> {
+        let Some(plugins) = self.plugins else {
+            return BTreeMap::new();
+        };
+        let (decoded, failures) = plugins.decode_logs(invoked_programs(logs), logs);
+        for (plugin, e) in failures {
+            self.plugin_failed(&plugin, e);
+        }
+        decoded
+    }
+
+    /// Decode base64 account data with the plugin of its owner program
+    pub fn decode_account(&self, owner: &str, data: &str) -> BTreeMap<String, serde_json::Value> {
+        let Some(plugins) = self.plugins else {
+            return BTreeMap::new();
+        };
+        let Ok(bytes) = BASE64.decode(data) else {
+            return BTreeMap::new();
+        };
+        match plugins.decode_account(owner, &bytes) {
+            Ok(Some(value)) => BTreeMap::from([(owner.to_string(), value)]),
+            Ok(None) => BTreeMap::new(),
+            Err((plugin, e)) => {
+                self.plugin_failed(&plugin, e);
+                BTreeMap::new()
+            }
+        }
+    }
+
+    /// A failing plugin only costs the decoded output, not the event
+    fn plugin_failed(&self, plugin: &str, e: anyhow::Error) {
+        warn!("Decoder plugin {} failed: {:#}", plugin, e);
+        self.metrics
+            .plugin_errors_total
+            .with_label_values(&[plugin])
+            .inc();
+    }
+
+    /// Run the script hook, then wrap the event in an envelope, write it to
+    /// every sink, and track its commitment
+    ///
+    /// Returns `false` if the script dropped the event.
+    pub async fn publish(
+        &self,
+        event: impl Into<Event>,
+        subscription: u64,
+        received_at: OffsetDateTime,
+        block_time: Option<OffsetDateTime>,
+    ) -> Result<bool> {
+        let mut event = event.into();
+        if let Some(script) = self.script {
+            // A failing script must not lose data, so the event is stored unchanged
+            match script.apply(&event) {
+                Ok(ScriptOutcome::Pass) => {}
+                Ok(ScriptOutcome::Replace(replaced)) => event = *replaced,
+                Ok(ScriptOutcome::Drop) => {
+                    self.metrics
+                        .events_filtered_total
+                        .with_label_values(&["script"])
+                        .inc();
+                    return Ok(false);
+                }
+                Err(e) => {
+                    warn!("Script hook failed, storing event unchanged: {:#}", e);
+                    self.metrics.script_errors_total.inc();
+                }
+            }
+        }
+
+        let envelope = self.source.wrap(event, subscription, received_at, block_time)?;
+        self.store(&envelope).await?;
+
+        self.metrics.events_total.inc();
+        if let Some(tracker) = self.tracker {
+            tracker.track(&envelope);
+        }
+        Ok(true)
+    }
+
+    /// Write an envelope to every sink and raise its alert
+    pub async fn store(&self, envelope: &Envelope) -> Result<()> {
+        self.sinks
+            .write_event(envelope)
+            .await
+            .context("Failed to write event")?;
+
+        self.notifications.offer(envelope);
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+//! WebAssembly decoder plugins
+//!
+//! Every `*.wasm` file in the plugin directory is loaded at startup and
+//! registered for the program id it reports. Modules run sandboxed: they may
+//! not import anything, each call gets a fresh instance with a fuel budget and
+//! a memory cap, and a failing call only loses that event's decoded output.
+//!
+//! # ABI
+//!
+//! Strings are UTF-8. A "slice" is an `i64` packing `(ptr << 32) | len` into
+//! the module's memory; `0` means no output.
+//!
+//! | Export | Signature | Purpose |
+//! |--------|-----------|---------|
+//! | `memory` | memory | Linear memory shared with the host |
+//! | `alloc` | `(len: i32) -> i32` | Buffer for the host to write input into |
+//! | `program_id` | `() -> i64` | Slice holding the base58 program id decoded |
+//! | `decode_logs` | `(ptr: i32, len: i32) -> i64` | Log lines joined by `\n` in, JSON slice out |
+//! | `decode_account` | `(ptr: i32, len: i32) -> i64` | Raw account data in, JSON slice out |
+//!
+//! Either decode export may be omitted.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tracing::info;
+use wasmi::{Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// Linear memory a plugin may grow to
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Largest JSON output accepted from a plugin
+const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Input kinds, named after the export that decodes them
+#[derive(Debug, Clone, Copy)]
+enum Input {
+    Logs,
+    Account,
+}
+
+impl Input {
+    fn export(&self) -> &'static str {
+        match self {
+            Input::Logs => "decode_logs",
+            Input::Account => "decode_account",
+        }
+    }
+}
+
+/// A compiled decoder module
+struct Plugin {
+    /// File stem, used in logs and metrics
+    name: String,
+    module: Module,
+}
+
+/// Decoder plugins keyed by program id
+pub struct PluginRegistry {
+    engine: Engine,
+    fuel: u64,
+    plugins: HashMap<String, Plugin>,
+}
+
+impl PluginRegistry {
+    /// Load every `*.wasm` module in `dir`, allowing `fuel` per call
+    pub fn load_dir(dir: &Path, fuel: u64) -> Result<Self> {
+        let mut registry = Self::new(fuel);
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read plugin directory {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let wasm = std::fs::read(&path)
+                .with_context(|| format!("Failed to read plugin {}", path.display()))?;
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let program_id = registry
+                .register(&name, &wasm)
+                .with_context(|| format!("Failed to load plugin {}", path.display()))?;
+            info!("Loaded decoder plugin {} for program {}", name, program_id);
+        }
+        Ok(registry)
+    }
+
+    fn new(fuel: u64) -> Self {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        Self {
+            engine: Engine::new(&config),
+            fuel: fuel.max(1),
+            plugins: HashMap::new(),
+        }
+    }
+
+    /// Compile a module and register it for the program id it reports
+    fn register(&mut self, name: &str, wasm: &[u8]) -> Result<String> {
+        let module = Module::new(&self.engine, wasm).map_err(|e| anyhow!("{}", e))?;
+        if module.imports().len() > 0 {
+            anyhow::bail!("Plugins may not import host functions");
+        }
+
+        let plugin = Plugin {
+            name: name.to_string(),
+            module,
+        };
+        let (mut store, instance) = self.instantiate(&plugin)?;
+        let program_id = instance
+            .get_typed_func::<(), i64>(&store, "program_id")
+            .map_err(|e| anyhow!("Missing program_id export: {}", e))?
+            .call(&mut store, ())
+            .map_err(|e| anyhow!("program_id failed: {}", e))?;
+        let program_id = String::from_utf8(read_slice(&store, &instance, program_id)?)
+            .context("program_id is not UTF-8")?;
+        if program_id.is_empty() {
+            anyhow::bail!("program_id returned nothing");
+        }
+
+        if let Some(existing) = self.plugins.get(&program_id) {
+            anyhow::bail!("Program {} is already decoded by {}", program_id, existing.name);
+        }
+        self.plugins.insert(program_id.clone(), plugin);
+        Ok(program_id)
+    }
+
+    /// Whether no plugins are loaded
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Decode log lines with the plugin of every listed program that has one
+    ///
+    /// Returns program id -> decoded JSON, and the names of plugins that failed.
+    pub fn decode_logs<'a>(
+        &self,
+        programs: impl IntoIterator<Item = &'a str>,
+        logs: &[String],
+    ) -> (BTreeMap<String, serde_json::Value>, Vec<(String, anyhow::Error)>) {
+        let input = logs.join("\n");
+        let mut decoded = BTreeMap::new();
+        let mut failures = Vec::new();
+        for program_id in programs {
+            let Some(plugin) = self.plugins.get(program_id) else {
+                continue;
+            };
+            match self.call(plugin, Input::Logs, input.as_bytes()) {
+                Ok(Some(value)) => {
+                    decoded.insert(program_id.to_string(), value);
+                }
+                Ok(None) => {}
+                Err(e) => failures.push((plugin.name.clone(), e)),
+            }
+        }
+        (decoded, failures)
+    }
+
+    /// Decode account data with the plugin registered for its owner program
+    ///
+    /// Returns the plugin name alongside any error.
+    pub fn decode_account(
+        &self,
+        owner: &str,
+        data: &[u8],
+    ) -> Result<Option<serde_json::Value>, (String, anyhow::Error)> {
+        let Some(plugin) = self.plugins.get(owner) else {
+            return Ok(None);
+        };
+        self.call(plugin, Input::Account, data)
+            .map_err(|e| (plugin.name.clone(), e))
+    }
+
+    /// Run one decode export on a fresh instance
+    fn call(&self, plugin: &Plugin, input: Input, bytes: &[u8]) -> Result<Option<serde_json::Value>> {
+        let (mut store, instance) = self.instantiate(plugin)?;
+        let Ok(decode) = instance.get_typed_func::<(i32, i32), i64>(&store, input.export()) else {
+            return Ok(None);
+        };
+        let memory = instance
+            .get_memory(&store, "memory")
+            .context("Missing memory export")?;
+        let len = i32::try_from(bytes.len()).context("Input too large")?;
+
+        let ptr = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(|e| anyhow!("Missing alloc export: {}", e))?
+            .call(&mut store, len)
+            .map_err(|e| anyhow!("alloc failed: {}", e))?;
+        memory
+            .write(&mut store, ptr as u32 as usize, bytes)
+            .map_err(|e| anyhow!("alloc returned an invalid buffer: {}", e))?;
+
+        let output = decode
+            .call(&mut store, (ptr, len))
+            .map_err(|e| anyhow!("{} failed: {}", input.export(), e))?;
+        if output == 0 {
+            return Ok(None);
+        }
+        let json = read_slice(&store, &instance, output)?;
+        serde_json::from_slice(&json)
+            .map(Some)
+            .with_context(|| format!("{} returned invalid JSON", input.export()))
+    }
+
+    fn instantiate(&self, plugin: &Plugin) -> Result<(Store<StoreLimits>, Instance)> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel).map_err(|e| anyhow!("{}", e))?;
+
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &plugin.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| anyhow!("Failed to instantiate: {}", e))?;
+        Ok((store, instance))
+    }
+}
+
+/// Copy a packed `(ptr << 32) | len` slice out of the module's memory
+fn read_slice(store: &Store<StoreLimits>, instance: &Instance, packed: i64) -> Result<Vec<u8>> {
+    let ptr = (packed as u64 >> 32) as usize;
+    let len = (packed as u64 & 0xffff_ffff) as usize;
+    if len > MAX_OUTPUT_BYTES {
+        anyhow::bail!("Output of {} bytes exceeds the {} byte limit", len, MAX_OUTPUT_BYTES);
+    }
+
+    let memory = instance
+        .get_memory(store, "memory")
+        .context("Missing memory export")?;
+    let mut buf = vec![0u8; len];
+    memory
+        .read(store, ptr, &mut buf)
+        .map_err(|e| anyhow!("Output slice out of bounds: {}", e))?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Echoes log input back as the decoded JSON and answers a fixed account payload
+    const ECHO: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "Prog1111111111111111111111111111111111111111")
+          (data (i32.const 64) "{\"kind\":\"account\"}")
+          (func (export "alloc") (param i32) (result i32) i32.const 1024)
+          (func (export "program_id") (result i64) i64.const 44)
+          (func (export "decode_logs") (param i32 i32) (result i64)
+            local.get 0
+            i64.extend_i32_u
+            i64.const 32
+            i64.shl
+            local.get 1
+            i64.extend_i32_u
+            i64.or)
+          (func (export "decode_account") (param i32 i32) (result i64)
+            i64.const 274877906962))
+    "#;
+
+    /// Spins forever when decoding logs
+    const SPIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (data (i32.const 0) "Spin")
+          (func (export "alloc") (param i32) (result i32) i32.const 1024)
+          (func (export "program_id") (result i64) i64.const 4)
+          (func (export "decode_logs") (param i32 i32) (result i64)
+            (loop $forever (br $forever))
+            i64.const 0))
+    "#;
+
+    const PROGRAM: &str = "Prog1111111111111111111111111111111111111111";
+
+    fn registry() -> PluginRegistry {
+        let mut registry = PluginRegistry::new(100_000);
+        registry.register("echo", &wat::parse_str(ECHO).unwrap()).unwrap();
+        registry.register("spin", &wat::parse_str(SPIN).unwrap()).unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_decode_logs_for_invoked_programs() {
+        let registry = registry();
+        let logs = vec![r#"{"amount":42}"#.to_string()];
+
+        let (decoded, failures) = registry.decode_logs([PROGRAM, "Unknown111"], &logs);
+        assert!(failures.is_empty());
+        assert_eq!(decoded[PROGRAM]["amount"], 42);
+        assert_eq!(decoded.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_account_by_owner() {
+        let registry = registry();
+        let decoded = registry.decode_account(PROGRAM, &[1, 2, 3]).unwrap().unwrap();
+        assert_eq!(decoded["kind"], "account");
+        assert!(registry.decode_account("Unknown111", &[]).unwrap().is_none());
+        // The spin plugin has no decode_account export
+        assert!(registry.decode_account("Spin", &[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_fuel_limits_runaway_plugins() {
+        let registry = registry();
+        let (decoded, failures) = registry.decode_logs(["Spin"], &[]);
+        assert!(decoded.is_empty());
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "spin");
+    }
+
+    #[test]
+    fn test_register_rejects_imports_and_duplicates() {
+        let mut registry = registry();
+        assert!(registry.register("again", &wat::parse_str(ECHO).unwrap()).is_err());
+
+        let importer = r#"(module (import "env" "now" (func)) (memory (export "memory") 1))"#;
+        assert!(registry.register("importer", &wat::parse_str(importer).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("echo.wasm"), wat::parse_str(ECHO).unwrap()).unwrap();
+        std::fs::write(dir.path().join("README.txt"), "not a plugin").unwrap();
+
+        let registry = PluginRegistry::load_dir(dir.path(), 100_000).unwrap();
+        assert!(!registry.is_empty());
+        assert_eq!(registry.plugins[PROGRAM].name, "echo");
+    }
+}
@@ -2,12 +2,12 @@
 
 use anyhow::{Context, Result};
 use crate::config::Config;
-use crate::event::{AccountEvent, LogEvent};
-use crate::metrics::MetricsRegistry;
-use crate::storage::JsonlWriter;
+use crate::event::{format_timestamp, AccountEvent, LogEvent, TransactionError};
+use crate::pipeline::Pipeline;
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
 use std::time::Duration;
 use time::OffsetDateTime;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
@@ -27,7 +27,6 @@ struct RpcRequest {
 struct RpcResponse {
     #[allow(dead_code)]
     jsonrpc: String,
-    #[allow(dead_code)]
     id: u64,
     #[serde(default)]
     result: Option<serde_json::Value>,
@@ -42,12 +41,23 @@ struct RpcError {
     message: String,
 }
 
-/// Logs notification payload
+/// Subscription notification: `{"method": ..., "params": {"result": ..., "subscription": id}}`
 #[derive(Debug, Deserialize)]
-struct LogsNotification {
-    result: LogsNotificationResult,
+struct Notification<T> {
+    params: NotificationParams<T>,
 }
 
+/// Notification params
+#[derive(Debug, Deserialize)]
+struct NotificationParams<T> {
+    result: T,
+    /// Server-assigned subscription id
+    subscription: u64,
+}
+
+/// Logs notification payload
+type LogsNotification = Notification<LogsNotificationResult>;
+
 /// Logs notification result
 #[derive(Debug, Deserialize)]
 struct LogsNotificationResult {
@@ -64,31 +74,27 @@ struct NotificationContext {
 /// Logs notification value
 #[derive(Debug, Deserialize)]
 struct LogsNotificationValue {
-    #[allow(dead_code)]
     err: Option<serde_json::Value>,
     logs: Vec<String>,
     signature: String,
 }
 
 /// Calculate exponential backoff delay with max cap
-fn calculate_backoff(attempt: u32, max_seconds: u64) -> Duration {
+pub(crate) fn calculate_backoff(attempt: u32, max_seconds: u64) -> Duration {
     let delay_secs = (1u64 << attempt.min(5)).min(max_seconds);
     Duration::from_secs(delay_secs)
 }
 
 /// Run logs subscription with automatic reconnection
-pub async fn run_logs_subscribe(
-    config: &Config,
-    writer: JsonlWriter,
-    metrics: MetricsRegistry,
-) -> Result<()> {
+pub async fn run_logs_subscribe(config: &Config, pipeline: &Pipeline<'_>) -> Result<()> {
     let ws_url = &config.ws_url;
     let program_id = config.program_id.as_ref().unwrap();
     let commitment = config.commitment.as_str();
+    let metrics = pipeline.metrics;
 
     let mut attempt = 0u32;
     loop {
-        match try_logs_subscribe(ws_url, program_id, commitment, &writer, &metrics).await {
+        match try_logs_subscribe(ws_url, program_id, commitment, pipeline).await {
             Ok(()) => {
                 info!("Logs subscription loop exited normally");
                 break Ok(());
@@ -119,9 +125,9 @@ async fn try_logs_subscribe(
     ws_url: &str,
     program_id: &str,
     commitment: &str,
-    writer: &JsonlWriter,
-    metrics: &MetricsRegistry,
+    pipeline: &Pipeline<'_>,
 ) -> Result<()> {
+    let metrics = pipeline.metrics;
     info!("Connecting to Solana WebSocket: {}", ws_url);
     
     // Set connected gauge to 0 initially
@@ -167,7 +173,7 @@ async fn try_logs_subscribe(
             Ok(Message::Text(text)) => {
                 trace!("Received message: {}", text);
                 
-                if let Err(e) = handle_message(&text, writer, metrics).await {
+                if let Err(e) = handle_message(&text, pipeline).await {
                     error!("Error handling message: {}", e);
                     metrics.errors_total.inc();
                 }
@@ -206,11 +212,7 @@ async fn try_logs_subscribe(
 }
 
 /// Handle incoming WebSocket message
-async fn handle_message(
-    text: &str,
-    writer: &JsonlWriter,
-    metrics: &MetricsRegistry,
-) -> Result<()> {
+async fn handle_message(text: &str, pipeline: &Pipeline<'_>) -> Result<()> {
     // Try to parse as RPC response first
     if let Ok(response) = serde_json::from_str::<RpcResponse>(text) {
         // This is likely a subscription confirmation
@@ -225,7 +227,7 @@ async fn handle_message(
 
     // Try to parse as notification
     if let Ok(notification) = serde_json::from_str::<LogsNotification>(text) {
-        handle_logs_notification(notification, writer, metrics).await?;
+        handle_logs_notification(notification, pipeline).await?;
         return Ok(());
     }
 
@@ -237,12 +239,25 @@ async fn handle_message(
 /// Handle logs notification
 async fn handle_logs_notification(
     notification: LogsNotification,
-    writer: &JsonlWriter,
-    metrics: &MetricsRegistry,
+    pipeline: &Pipeline<'_>,
 ) -> Result<()> {
-    let slot = notification.result.context.slot;
-    let signature = &notification.result.value.signature;
-    let logs = &notification.result.value.logs;
+    let received_at = OffsetDateTime::now_utc();
+    let subscription = notification.params.subscription;
+    let result = &notification.params.result;
+    let slot = result.context.slot;
+    let signature = &result.value.signature;
+    let logs = &result.value.logs;
+    let err = result.value.err.clone().map(TransactionError::decode);
+
+    if let Some(rejection) = pipeline.log_filter.check(logs, err.is_some()) {
+        trace!("Skipping transaction {} ({} filter)", signature, rejection.as_str());
+        pipeline
+            .metrics
+            .events_filtered_total
+            .with_label_values(&[rejection.as_str()])
+            .inc();
+        return Ok(());
+    }
 
     // Get program ID from logs (first log line usually)
     let program_id = logs
@@ -257,42 +272,69 @@ async fn handle_logs_notification(
         })
         .unwrap_or_else(|| "unknown".to_string());
 
-    // Create timestamp
-    let timestamp = OffsetDateTime::now_utc()
-        .format(&time::format_description::well_known::Rfc3339)
-        .context("Failed to format timestamp")?;
+    // Timestamp with the on-chain block time, falling back to receipt time
+    let block_time = pipeline.block_time(slot, received_at).await;
+    let timestamp = format_timestamp(block_time.unwrap_or(received_at))?;
+
+    let status = err.as_ref().map_or_else(|| "ok".to_string(), |err| err.name.clone());
 
     // Create log event
-    let event = LogEvent::new(
-        timestamp,
-        signature.clone(),
-        slot,
-        program_id.clone(),
-        logs.clone(),
-    );
+    let event = LogEvent {
+        err,
+        decoded: pipeline.decode_logs(logs),
+        ..LogEvent::new(
+            timestamp,
+            signature.clone(),
+            slot,
+            program_id.clone(),
+            logs.clone(),
+        )
+    };
 
     // Write to storage
-    writer.write(&event).await.context("Failed to write event")?;
-
-    // Increment metrics
-    metrics.events_total.inc();
+    if !pipeline
+        .publish(event, subscription, received_at, block_time)
+        .await?
+    {
+        return Ok(());
+    }
 
     // Log event
     info!(
-        "Event: signature={}, slot={}, program={}, log_lines={}",
+        "Event: signature={}, slot={}, program={}, log_lines={}, status={}",
         signature,
         slot,
         program_id,
-        logs.len()
+        logs.len(),
+        status
     );
 
     Ok(())
 }
 
 /// Account notification payload
-#[derive(Debug, Deserialize)]
-struct AccountNotification {
-    result: AccountNotificationResult,
+type AccountNotification = Notification<AccountNotificationResult>;
+
+/// Maps server subscription ids back to the subscribed account
+#[derive(Debug, Default)]
+struct AccountSubscriptions {
+    /// Request id -> account, until the server confirms
+    pending: HashMap<u64, String>,
+    /// Subscription id -> account
+    active: HashMap<u64, String>,
+}
+
+impl AccountSubscriptions {
+    /// Record the subscription id the server assigned to a request
+    fn confirm(&mut self, request_id: u64, subscription: u64) -> Option<&str> {
+        let account = self.pending.remove(&request_id)?;
+        Some(self.active.entry(subscription).or_insert(account))
+    }
+
+    /// Account a notification belongs to
+    fn account(&self, subscription: u64) -> Option<&str> {
+        self.active.get(&subscription).map(String::as_str)
+    }
 }
 
 /// Account notification result
@@ -302,29 +344,22 @@ struct AccountNotificationResult {
     value: AccountNotificationValue,
 }
 
-/// Account notification value
+/// Account notification value: the account itself
 #[derive(Debug, Deserialize)]
 struct AccountNotificationValue {
-    account: AccountData,
-}
-
-/// Account data
-#[derive(Debug, Deserialize)]
-struct AccountData {
-    #[serde(rename = "lamports")]
     lamports: u64,
-    data: Vec<String>,  // base64 encoded
+    /// `[data, encoding]`; subscriptions request base64
+    data: Vec<String>,
+    /// Program that owns the account
+    owner: String,
 }
 
 /// Run account subscription with automatic reconnection
-pub async fn run_account_subscribe(
-    config: &Config,
-    writer: JsonlWriter,
-    metrics: MetricsRegistry,
-) -> Result<()> {
+pub async fn run_account_subscribe(config: &Config, pipeline: &Pipeline<'_>) -> Result<()> {
     let ws_url = &config.ws_url;
     let accounts = config.parse_accounts()?;
     let commitment = config.commitment.as_str();
+    let metrics = pipeline.metrics;
 
     if accounts.is_empty() {
         anyhow::bail!("No accounts provided for account subscription");
@@ -332,7 +367,7 @@ pub async fn run_account_subscribe(
 
     let mut attempt = 0u32;
     loop {
-        match try_account_subscribe(ws_url, &accounts, commitment, &writer, &metrics).await {
+        match try_account_subscribe(ws_url, &accounts, commitment, pipeline).await {
             Ok(()) => {
                 info!("Account subscription loop exited normally");
                 break Ok(());
@@ -363,9 +398,9 @@ async fn try_account_subscribe(
     ws_url: &str,
     accounts: &[String],
     commitment: &str,
-    writer: &JsonlWriter,
-    metrics: &MetricsRegistry,
+    pipeline: &Pipeline<'_>,
 ) -> Result<()> {
+    let metrics = pipeline.metrics;
     info!("Connecting to Solana WebSocket: {}", ws_url);
     
     metrics.ws_connected.set(0.0);
@@ -381,9 +416,10 @@ async fn try_account_subscribe(
     // Split the stream for read/write
     let (mut write, mut read) = ws_stream.split();
 
-    // Subscribe to all accounts
-    let mut subscription_id = 1u64;
-    for account in accounts {
+    // Subscribe to all accounts, remembering which request id was for which account
+    let mut subscriptions = AccountSubscriptions::default();
+    for (subscription_id, account) in (1u64..).zip(accounts) {
+        subscriptions.pending.insert(subscription_id, account.clone());
         let subscribe_request = RpcRequest {
             jsonrpc: "2.0".to_string(),
             id: subscription_id,
@@ -402,8 +438,6 @@ async fn try_account_subscribe(
             .send(Message::Text(subscribe_msg))
             .await
             .context("Failed to send subscription request")?;
-
-        subscription_id += 1;
     }
 
     info!("Subscribed to {} accounts", accounts.len());
@@ -414,7 +448,7 @@ async fn try_account_subscribe(
             Ok(Message::Text(text)) => {
                 trace!("Received message: {}", text);
                 
-                if let Err(e) = handle_account_message(&text, writer, metrics).await {
+                if let Err(e) = handle_account_message(&text, &mut subscriptions, pipeline).await {
                     error!("Error handling message: {}", e);
                     metrics.errors_total.inc();
                 }
@@ -455,23 +489,30 @@ async fn try_account_subscribe(
 /// Handle incoming WebSocket message for account subscriptions
 async fn handle_account_message(
     text: &str,
-    writer: &JsonlWriter,
-    metrics: &MetricsRegistry,
+    subscriptions: &mut AccountSubscriptions,
+    pipeline: &Pipeline<'_>,
 ) -> Result<()> {
     // Try to parse as RPC response first
     if let Ok(response) = serde_json::from_str::<RpcResponse>(text) {
         if let Some(ref error) = response.error {
             anyhow::bail!("RPC error: {} (code: {})", error.message, error.code);
         }
-        if let Some(ref result) = response.result {
-            info!("Subscription confirmed: {}", result);
+        if let Some(subscription) = response.result.as_ref().and_then(|r| r.as_u64()) {
+            match subscriptions.confirm(response.id, subscription) {
+                Some(account) => info!("Subscription {} confirmed for {}", subscription, account),
+                None => info!("Subscription confirmed: {}", subscription),
+            }
         }
         return Ok(());
     }
 
     // Try to parse as account notification
     if let Ok(notification) = serde_json::from_str::<AccountNotification>(text) {
-        handle_account_notification(notification, writer, metrics).await?;
+        let pubkey = subscriptions
+            .account(notification.params.subscription)
+            .unwrap_or("unknown")
+            .to_string();
+        handle_account_notification(notification, pubkey, pipeline).await?;
         return Ok(());
     }
 
@@ -483,37 +524,33 @@ async fn handle_account_message(
 /// Handle account notification
 async fn handle_account_notification(
     notification: AccountNotification,
-    writer: &JsonlWriter,
-    metrics: &MetricsRegistry,
+    pubkey: String,
+    pipeline: &Pipeline<'_>,
 ) -> Result<()> {
-    let slot = notification.result.context.slot;
-    let lamports = notification.result.value.account.lamports;
-    let data = notification.result.value.account.data.join("");
-
-    // Note: We don't have the pubkey directly in the notification
-    // This is a limitation of the current implementation
-    // In a real implementation, we'd need to track subscription IDs to pubkeys
-    let pubkey = "unknown".to_string();
+    let received_at = OffsetDateTime::now_utc();
+    let subscription = notification.params.subscription;
+    let account = &notification.params.result.value;
+    let slot = notification.params.result.context.slot;
+    let lamports = account.lamports;
+    let data = account.data.first().cloned().unwrap_or_default();
 
-    // Create timestamp
-    let timestamp = OffsetDateTime::now_utc()
-        .format(&time::format_description::well_known::Rfc3339)
-        .context("Failed to format timestamp")?;
+    // Timestamp with the on-chain block time, falling back to receipt time
+    let block_time = pipeline.block_time(slot, received_at).await;
+    let timestamp = format_timestamp(block_time.unwrap_or(received_at))?;
 
     // Create account event
-    let event = AccountEvent::new(
-        timestamp,
-        pubkey.clone(),
-        slot,
-        lamports,
-        data,
-    );
+    let event = AccountEvent {
+        decoded: pipeline.decode_account(&account.owner, &data),
+        ..AccountEvent::new(timestamp, pubkey.clone(), slot, lamports, data)
+    };
 
     // Write to storage
-    writer.write(&event).await.context("Failed to write event")?;
-
-    // Increment metrics
-    metrics.events_total.inc();
+    if !pipeline
+        .publish(event, subscription, received_at, block_time)
+        .await?
+    {
+        return Ok(());
+    }
 
     // Log event
     info!(
@@ -546,4 +583,54 @@ mod tests {
         assert_eq!(calculate_backoff(3, 10), Duration::from_secs(8));
         assert_eq!(calculate_backoff(4, 10), Duration::from_secs(10));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_logs_notification() {
+        let text = r#"{"jsonrpc":"2.0","method":"logsNotification","params":{"result":{"context":{"slot":5208469},"value":{"signature":"5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv","err":null,"logs":["Program 11111111111111111111111111111111 invoke [1]"]}},"subscription":24040}}"#;
+
+        assert!(serde_json::from_str::<RpcResponse>(text).is_err());
+        let notification: LogsNotification = serde_json::from_str(text).unwrap();
+        assert_eq!(notification.params.subscription, 24040);
+        assert_eq!(notification.params.result.context.slot, 5208469);
+        assert_eq!(notification.params.result.value.logs.len(), 1);
+        assert!(notification.params.result.value.err.is_none());
+    }
+
+    #[test]
+    fn test_parse_failed_logs_notification() {
+        let text = r#"{"jsonrpc":"2.0","method":"logsNotification","params":{"result":{"context":{"slot":5208469},"value":{"signature":"5h6x...","err":{"InstructionError":[2,{"Custom":6001}]},"logs":["Program log: Error: slippage exceeded"]}},"subscription":24040}}"#;
+
+        let notification: LogsNotification = serde_json::from_str(text).unwrap();
+        let err = TransactionError::decode(notification.params.result.value.err.unwrap());
+        assert_eq!(err.instruction_index, Some(2));
+        assert_eq!(err.custom_code, Some(6001));
+    }
+
+    #[test]
+    fn test_parse_account_notification() {
+        let text = r#"{"jsonrpc":"2.0","method":"accountNotification","params":{"result":{"context":{"slot":5199307},"value":{"data":["AQID","base64"],"executable":false,"lamports":33594,"owner":"11111111111111111111111111111111","rentEpoch":635,"space":3}},"subscription":23784}}"#;
+
+        let notification: AccountNotification = serde_json::from_str(text).unwrap();
+        let account = &notification.params.result.value;
+        assert_eq!(notification.params.result.context.slot, 5199307);
+        assert_eq!(account.lamports, 33594);
+        assert_eq!(account.data[0], "AQID");
+        assert_eq!(account.owner, "11111111111111111111111111111111");
+    }
+
+    #[test]
+    fn test_account_subscriptions_map_to_pubkeys() {
+        let mut subscriptions = AccountSubscriptions::default();
+        subscriptions.pending.insert(1, "AccountA".to_string());
+        subscriptions.pending.insert(2, "AccountB".to_string());
+
+        let response: RpcResponse =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","result":23784,"id":2}"#).unwrap();
+        let subscription = response.result.unwrap().as_u64().unwrap();
+        assert_eq!(subscriptions.confirm(response.id, subscription), Some("AccountB"));
+
+        assert_eq!(subscriptions.account(23784), Some("AccountB"));
+        assert_eq!(subscriptions.account(1), None);
+        assert_eq!(subscriptions.confirm(2, 99), None);
+    }
+}
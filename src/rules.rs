@@ -0,0 +1,449 @@
+//! Notification rules loaded from a TOML file
+//!
+//! Each rule is a condition on stored events plus where to send the alert it
+//! raises and how long the alert manager groups its later alerts:
+//!
+//! ```toml
+//! [[rules]]
+//! name = "large-liquidation"
+//! notifiers = ["slack", "pagerduty"]
+//! cooldown_secs = 60
+//!
+//! [rules.when]
+//! kind = "decoded_field"
+//! program = "Lend1111111111111111111111111111111111111"
+//! field = "amount"
+//! greater_than = 1_000_000_000
+//! ```
+
+use crate::alert::{describe, Alert, Severity};
+use crate::alert_manager::Routed;
+use crate::event::{Envelope, Event};
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Condition a rule checks, selected by its `kind`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Condition {
+    /// An account's balance fell by more than `more_than` lamports between
+    /// two updates
+    LamportsDrop { account: String, more_than: u64 },
+    /// More than `more_than` events within `within_secs`, optionally only
+    /// for one program
+    EventRate {
+        program: Option<String>,
+        more_than: usize,
+        within_secs: u64,
+    },
+    /// A log line matches `regex`
+    LogMatch {
+        program: Option<String>,
+        regex: String,
+    },
+    /// A decoded field (dot-separated path) is above or below a threshold
+    DecodedField {
+        program: Option<String>,
+        field: String,
+        greater_than: Option<f64>,
+        less_than: Option<f64>,
+    },
+}
+
+/// A rule as written in the rules file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    pub name: String,
+    pub when: Condition,
+    /// Notifiers to route to by name; every notifier when empty
+    #[serde(default)]
+    pub notifiers: Vec<String>,
+    /// Minimum time between two alerts of this rule, overriding
+    /// `ALERT_COOLDOWN_SECS`; alerts in between are summarized
+    #[serde(default)]
+    pub cooldown_secs: Option<u64>,
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+}
+
+fn default_severity() -> Severity {
+    Severity::Warning
+}
+
+/// Layout of the rules file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<RuleConfig>,
+}
+
+/// Mutable state of a rule
+#[derive(Debug, Default)]
+struct RuleState {
+    /// Last balance seen, for `lamports_drop`
+    lamports: Option<u64>,
+    /// Times of recent matching events, for `event_rate`
+    recent: VecDeque<Instant>,
+}
+
+/// Compiled rule
+#[derive(Debug)]
+struct Rule {
+    config: RuleConfig,
+    regex: Option<Regex>,
+}
+
+/// Rules evaluated against every stored event
+#[derive(Debug)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    state: Mutex<HashMap<String, RuleState>>,
+}
+
+impl RuleSet {
+    /// Load and validate a rules file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rules file: {}", path.display()))?;
+        let file: RulesFile = toml::from_str(&text)
+            .with_context(|| format!("Invalid rules file: {}", path.display()))?;
+        Self::new(file.rules)
+    }
+
+    pub fn new(configs: Vec<RuleConfig>) -> Result<Self> {
+        let mut rules = Vec::new();
+        let mut names = HashMap::new();
+        for config in configs {
+            anyhow::ensure!(!config.name.is_empty(), "Rule names must not be empty");
+            anyhow::ensure!(
+                names.insert(config.name.clone(), ()).is_none(),
+                "Duplicate rule name: {}",
+                config.name
+            );
+            let regex = match &config.when {
+                Condition::LogMatch { regex, .. } => Some(
+                    Regex::new(regex)
+                        .with_context(|| format!("Rule {}: invalid regex: {}", config.name, regex))?,
+                ),
+                Condition::DecodedField {
+                    greater_than: None,
+                    less_than: None,
+                    ..
+                } => anyhow::bail!("Rule {}: set greater_than or less_than", config.name),
+                Condition::EventRate { within_secs: 0, .. } => {
+                    anyhow::bail!("Rule {}: within_secs must be positive", config.name)
+                }
+                _ => None,
+            };
+            rules.push(Rule { config, regex });
+        }
+
+        Ok(Self {
+            rules,
+            state: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Notifier names referenced by any rule
+    pub fn notifier_names(&self) -> impl Iterator<Item = &str> {
+        self.rules
+            .iter()
+            .flat_map(|rule| rule.config.notifiers.iter().map(String::as_str))
+    }
+
+    /// Alerts raised by an event received at `now`, grouped by rule name
+    pub fn evaluate(&self, envelope: &Envelope, now: Instant) -> Vec<Routed> {
+        let mut states = self.state.lock().unwrap();
+        let mut alerts = Vec::new();
+        for rule in &self.rules {
+            let state = states.entry(rule.config.name.clone()).or_default();
+            let Some(title) = rule.check(&envelope.event, state, now) else {
+                continue;
+            };
+
+            let alert = Alert {
+                body: describe(&envelope.event),
+                envelope: Some(envelope.clone()),
+                group: Some(rule.config.name.clone()),
+                ..Alert::new(
+                    rule.config.severity,
+                    format!("{}: {}", rule.config.name, title),
+                )
+            };
+            alerts.push(Routed {
+                cooldown: rule.config.cooldown_secs.map(Duration::from_secs),
+                ..Routed::new(alert, rule.config.notifiers.clone())
+            });
+        }
+        alerts
+    }
+}
+
+impl Rule {
+    /// Title of the alert if the event trips the rule
+    fn check(&self, event: &Event, state: &mut RuleState, now: Instant) -> Option<String> {
+        match &self.config.when {
+            Condition::LamportsDrop { account, more_than } => {
+                let Event::Account(event) = event else {
+                    return None;
+                };
+                if &event.pubkey != account {
+                    return None;
+                }
+                let previous = state.lamports.replace(event.lamports)?;
+                let drop = previous.saturating_sub(event.lamports);
+                (drop > *more_than).then(|| {
+                    format!(
+                        "{} lamports dropped by {} ({} -> {})",
+                        account, drop, previous, event.lamports
+                    )
+                })
+            }
+            Condition::EventRate {
+                program,
+                more_than,
+                within_secs,
+            } => {
+                if !matches!(event, Event::Log(_) | Event::Account(_))
+                    || !for_program(event, program.as_deref())
+                {
+                    return None;
+                }
+                let window = Duration::from_secs(*within_secs);
+                state.recent.push_back(now);
+                while state
+                    .recent
+                    .front()
+                    .is_some_and(|at| now.duration_since(*at) >= window)
+                {
+                    state.recent.pop_front();
+                }
+                (state.recent.len() > *more_than).then(|| {
+                    format!(
+                        "{} events{} within {}s",
+                        state.recent.len(),
+                        program.as_ref().map(|p| format!(" for {}", p)).unwrap_or_default(),
+                        within_secs
+                    )
+                })
+            }
+            Condition::LogMatch { program, .. } => {
+                let Event::Log(log) = event else {
+                    return None;
+                };
+                if !for_program(event, program.as_deref()) {
+                    return None;
+                }
+                let regex = self.regex.as_ref()?;
+                let line = log.logs.iter().find(|line| regex.is_match(line))?;
+                Some(format!("log matched: {}", line))
+            }
+            Condition::DecodedField {
+                program,
+                field,
+                greater_than,
+                less_than,
+            } => {
+                let decoded = match event {
+                    Event::Log(event) => &event.decoded,
+                    Event::Account(event) => &event.decoded,
+                    Event::Commitment(_) => return None,
+                };
+                let value = decoded
+                    .iter()
+                    .filter(|(id, _)| program.as_ref().is_none_or(|program| *id == program))
+                    .find_map(|(_, output)| number_at(output, field))?;
+                let above = greater_than.is_some_and(|threshold| value > threshold);
+                let below = less_than.is_some_and(|threshold| value < threshold);
+                if above {
+                    Some(format!("{} = {} > {}", field, value, greater_than.unwrap_or_default()))
+                } else if below {
+                    Some(format!("{} = {} < {}", field, value, less_than.unwrap_or_default()))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Whether the event belongs to `program`, or any program when `None`
+fn for_program(event: &Event, program: Option<&str>) -> bool {
+    let Some(program) = program else {
+        return true;
+    };
+    match event {
+        Event::Log(event) => event.program_id == program,
+        Event::Account(event) => event.decoded.contains_key(program),
+        Event::Commitment(event) => event.program_id.as_deref() == Some(program),
+    }
+}
+
+/// Number at a dot-separated path; numeric strings count
+fn number_at(value: &serde_json::Value, path: &str) -> Option<f64> {
+    let value = path
+        .split('.')
+        .try_fold(value, |value, key| value.get(key))?;
+    match value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{AccountEvent, LogEvent};
+    use serde_json::json;
+
+    fn rules(toml: &str) -> RuleSet {
+        let file: RulesFile = toml::from_str(toml).unwrap();
+        RuleSet::new(file.rules).unwrap()
+    }
+
+    fn account(lamports: u64) -> Envelope {
+        Envelope::test(AccountEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "Treasury".to_string(),
+            1,
+            lamports,
+            String::new(),
+        ))
+    }
+
+    fn log(program: &str, logs: &[&str], decoded: serde_json::Value) -> Envelope {
+        Envelope::test(LogEvent {
+            decoded: [(program.to_string(), decoded)].into(),
+            ..LogEvent::new(
+                "2024-01-15T10:30:45Z".to_string(),
+                "sig".to_string(),
+                1,
+                program.to_string(),
+                logs.iter().map(|line| line.to_string()).collect(),
+            )
+        })
+    }
+
+    #[test]
+    fn test_lamports_drop() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            name = "treasury-drain"
+            severity = "critical"
+            notifiers = ["pagerduty"]
+            when = { kind = "lamports_drop", account = "Treasury", more_than = 1000 }
+            "#,
+        );
+        let now = Instant::now();
+
+        assert!(rules.evaluate(&account(10_000), now).is_empty());
+        assert!(rules.evaluate(&account(9_500), now).is_empty());
+        let alerts = rules.evaluate(&account(8_000), now);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].alert.severity, Severity::Critical);
+        assert_eq!(
+            alerts[0].alert.title,
+            "treasury-drain: Treasury lamports dropped by 1500 (9500 -> 8000)"
+        );
+        assert_eq!(alerts[0].notifiers, vec!["pagerduty"]);
+    }
+
+    #[test]
+    fn test_event_rate() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            name = "burst"
+            cooldown_secs = 60
+
+            [rules.when]
+            kind = "event_rate"
+            program = "Lend"
+            more_than = 2
+            within_secs = 10
+            "#,
+        );
+        let start = Instant::now();
+        let event = log("Lend", &[], json!({}));
+        let second = Duration::from_secs(1);
+
+        assert!(rules.evaluate(&event, start).is_empty());
+        assert!(rules.evaluate(&event, start + second).is_empty());
+        // Other programs don't count
+        assert!(rules.evaluate(&log("Other", &[], json!({})), start + second).is_empty());
+        let alerts = rules.evaluate(&event, start + second * 2);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].alert.title, "burst: 3 events for Lend within 10s");
+        assert_eq!(alerts[0].alert.group.as_deref(), Some("burst"));
+        assert_eq!(alerts[0].cooldown, Some(Duration::from_secs(60)));
+        // Still bursting; the alert manager groups these
+        assert_eq!(rules.evaluate(&event, start + second * 3).len(), 1);
+        // The window slides
+        assert!(rules.evaluate(&event, start + second * 30).is_empty());
+    }
+
+    #[test]
+    fn test_log_match_and_decoded_field() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            name = "liquidation"
+            when = { kind = "log_match", regex = "Instruction: Liquidate" }
+
+            [[rules]]
+            name = "large-liquidation"
+            notifiers = ["slack"]
+
+            [rules.when]
+            kind = "decoded_field"
+            program = "Lend"
+            field = "args.amount"
+            greater_than = 1e6
+            "#,
+        );
+        let now = Instant::now();
+
+        let small = log("Lend", &["Program log: Instruction: Liquidate"], json!({"args": {"amount": 10}}));
+        let alerts = rules.evaluate(&small, now);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].alert.title, "liquidation: log matched: Program log: Instruction: Liquidate");
+
+        let large = log("Lend", &[], json!({"args": {"amount": "5000000"}}));
+        let alerts = rules.evaluate(&large, now);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].alert.title, "large-liquidation: args.amount = 5000000 > 1000000");
+        assert_eq!(alerts[0].alert.body, "Transaction sig in slot 1");
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let parse = |toml: &str| {
+            toml::from_str::<RulesFile>(toml)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| RuleSet::new(file.rules))
+        };
+        let rule = |name: &str, when: &str| format!("[[rules]]\nname = \"{}\"\nwhen = {{ {} }}\n", name, when);
+
+        assert!(parse(&rule("ok", r#"kind = "log_match", regex = "a""#)).is_ok());
+        assert!(parse(&rule("x", r#"kind = "log_match", regex = "(""#)).is_err());
+        assert!(parse(&rule("x", r#"kind = "decoded_field", field = "amount""#)).is_err());
+        assert!(parse(&rule("x", r#"kind = "unknown""#)).is_err());
+        // Typos are rejected rather than ignored
+        assert!(parse(&rule("x", r#"kind = "log_match", regex = "a", programm = "b""#)).is_err());
+        let duplicate = rule("x", r#"kind = "log_match", regex = "a""#).repeat(2);
+        assert!(parse(&duplicate).is_err());
+    }
+}
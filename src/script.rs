@@ -0,0 +1,308 @@
+//! Rhai script hooks for filtering and enriching events
+//!
+//! The script defines `fn on_event(kind, event)`, where `kind` is `"log"` or
+//! `"account"` and `event` is the decoded event as a map. It returns:
+//!
+//! - `()` or `false` to drop the event
+//! - `true` to keep it unchanged
+//! - a map to replace it; fields beyond the event's own are kept as extras
+//!
+//! A replacement can't change what identifies the event: `timestamp`, `slot`,
+//! `signature`, `program_id` and `err` of log events and `timestamp`, `slot`,
+//! `pubkey` and `lamports` of account events keep their original values.
+//!
+//! Only the hook function runs; the script's top level is never evaluated.
+//! Each call is limited to a fixed number of operations, so a runaway loop
+//! fails that call instead of stalling the listener. String, array and map
+//! sizes, call depth and expression nesting are capped as well, so a script
+//! can't exhaust memory or the stack within its operation budget.
+
+use crate::event::{AccountEvent, Event, LogEvent};
+use anyhow::{anyhow, Context, Result};
+use rhai::{CallFnOptions, Dynamic, Engine, Scope, AST};
+use std::path::Path;
+use tracing::{debug, info};
+
+/// Name of the function called for every event
+const HOOK_FN: &str = "on_event";
+
+/// Largest string a script may build, in bytes; a value's strings are counted
+/// together, so this leaves room for base64 data of the largest accounts
+const MAX_STRING_SIZE: usize = 32 * 1024 * 1024;
+
+/// Most elements in an array, counting nested arrays
+const MAX_ARRAY_SIZE: usize = 100_000;
+
+/// Most entries in an object map, counting nested maps
+const MAX_MAP_SIZE: usize = 100_000;
+
+/// Deepest chain of script function calls
+const MAX_CALL_LEVELS: usize = 32;
+
+/// Deepest expression nesting at the top level and inside functions
+const MAX_EXPR_DEPTHS: (usize, usize) = (64, 32);
+
+/// Outcome of running the hook on an event
+#[derive(Debug)]
+pub enum ScriptOutcome {
+    /// Store the event unchanged
+    Pass,
+    /// Store the script's replacement instead
+    Replace(Box<Event>),
+    /// Discard the event
+    Drop,
+}
+
+/// Compiled script with its sandboxed engine
+pub struct ScriptHook {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ScriptHook {
+    /// Compile the script at `path`, allowing `max_operations` per call
+    pub fn load(path: &Path, max_operations: u64) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script {}", path.display()))?;
+        Self::compile(&source, max_operations)
+            .with_context(|| format!("Invalid script {}", path.display()))
+    }
+
+    /// Compile a script from source
+    pub fn compile(source: &str, max_operations: u64) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations.max(1));
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_ARRAY_SIZE);
+        engine.set_max_map_size(MAX_MAP_SIZE);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_expr_depths(MAX_EXPR_DEPTHS.0, MAX_EXPR_DEPTHS.1);
+        engine.on_print(|text| info!("script: {}", text));
+        engine.on_debug(|text, _, pos| debug!("script {}: {}", pos, text));
+
+        let ast = engine.compile(source).map_err(|e| anyhow!("{}", e))?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == HOOK_FN && f.params.len() == 2)
+        {
+            anyhow::bail!("Script must define fn {}(kind, event)", HOOK_FN);
+        }
+
+        Ok(Self { engine, ast })
+    }
+
+    /// Run the hook on a log or account event; other events pass through
+    pub fn apply(&self, event: &Event) -> Result<ScriptOutcome> {
+        let kind = match event {
+            Event::Log(_) => "log",
+            Event::Account(_) => "account",
+            Event::Commitment(_) => return Ok(ScriptOutcome::Pass),
+        };
+        let input = rhai::serde::to_dynamic(event).map_err(|e| anyhow!("{}", e))?;
+
+        let mut options = CallFnOptions::new();
+        options.eval_ast = false;
+        let output: Dynamic = self
+            .engine
+            .call_fn_with_options(options, &mut Scope::new(), &self.ast, HOOK_FN, (kind, input))
+            .map_err(|e| anyhow!("{}", e))?;
+
+        if output.is_unit() {
+            return Ok(ScriptOutcome::Drop);
+        }
+        if let Ok(keep) = output.as_bool() {
+            return Ok(if keep {
+                ScriptOutcome::Pass
+            } else {
+                ScriptOutcome::Drop
+            });
+        }
+        if !output.is_map() {
+            anyhow::bail!("{} returned {}, expected a map, bool or ()", HOOK_FN, output.type_name());
+        }
+
+        let replaced = match event {
+            Event::Log(original) => Event::Log(LogEvent {
+                timestamp: original.timestamp.clone(),
+                signature: original.signature.clone(),
+                slot: original.slot,
+                program_id: original.program_id.clone(),
+                err: original.err.clone(),
+                ..from_output(&output)?
+            }),
+            Event::Account(original) => Event::Account(AccountEvent {
+                timestamp: original.timestamp.clone(),
+                pubkey: original.pubkey.clone(),
+                slot: original.slot,
+                lamports: original.lamports,
+                ..from_output(&output)?
+            }),
+            Event::Commitment(_) => unreachable!("commitment events are not scripted"),
+        };
+        Ok(ScriptOutcome::Replace(Box::new(replaced)))
+    }
+}
+
+/// Convert a returned map back into an event of the original kind
+fn from_output<T: serde::de::DeserializeOwned>(output: &Dynamic) -> Result<T> {
+    rhai::serde::from_dynamic(output)
+        .map_err(|e| anyhow!("{}", e))
+        .with_context(|| format!("{} returned an invalid event", HOOK_FN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_event(logs: &[&str]) -> Event {
+        LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "signature123".to_string(),
+            12345,
+            "program123".to_string(),
+            logs.iter().map(|line| line.to_string()).collect(),
+        )
+        .into()
+    }
+
+    const SCRIPT: &str = r#"
+        fn on_event(kind, event) {
+            if kind == "account" {
+                return event.lamports > 0;
+            }
+            if event.logs.len() == 0 {
+                return ();
+            }
+            event.team = "risk";
+            event.liquidation = event.logs.some(|line| line.contains("Liquidate"));
+            event
+        }
+    "#;
+
+    #[test]
+    fn test_script_enriches_events() {
+        let hook = ScriptHook::compile(SCRIPT, 10_000).unwrap();
+
+        let outcome = hook.apply(&log_event(&["Program log: Instruction: Liquidate"]));
+        let ScriptOutcome::Replace(replaced) = outcome.unwrap() else {
+            panic!("expected an enriched event");
+        };
+        let Event::Log(event) = *replaced else {
+            panic!("expected a log event");
+        };
+        assert_eq!(event.signature, "signature123");
+        assert_eq!(event.extra["team"], "risk");
+        assert_eq!(event.extra["liquidation"], true);
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["team"], "risk");
+    }
+
+    #[test]
+    fn test_script_drops_and_passes_events() {
+        let hook = ScriptHook::compile(SCRIPT, 10_000).unwrap();
+        assert!(matches!(hook.apply(&log_event(&[])).unwrap(), ScriptOutcome::Drop));
+
+        let funded = AccountEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "pubkey123".to_string(),
+            12345,
+            1_000_000,
+            "base64data".to_string(),
+        );
+        let closed = AccountEvent {
+            lamports: 0,
+            ..funded.clone()
+        };
+        assert!(matches!(hook.apply(&funded.into()).unwrap(), ScriptOutcome::Pass));
+        assert!(matches!(hook.apply(&closed.into()).unwrap(), ScriptOutcome::Drop));
+    }
+
+    #[test]
+    fn test_script_execution_budget() {
+        let hook = ScriptHook::compile("fn on_event(kind, event) { loop {} }", 1_000).unwrap();
+        let err = hook.apply(&log_event(&[])).unwrap_err();
+        assert!(err.to_string().contains("Too many operations"));
+    }
+
+    #[test]
+    fn test_script_size_limits() {
+        // Doubling a string reaches the size limit long before the budget runs out
+        let hook = ScriptHook::compile(
+            "fn on_event(kind, event) { let s = \"x\"; loop { s += s; } }",
+            1_000_000,
+        )
+        .unwrap();
+        let err = hook.apply(&log_event(&[])).unwrap_err();
+        assert!(err.to_string().contains("Length of string too large"), "{}", err);
+
+        let hook =
+            ScriptHook::compile("fn on_event(kind, event) { on_event(kind, event) }", 1_000_000)
+                .unwrap();
+        let err = hook.apply(&log_event(&[])).unwrap_err();
+        assert!(err.to_string().contains("Stack overflow"), "{}", err);
+    }
+
+    #[test]
+    fn test_script_cannot_change_identity_fields() {
+        let hook = ScriptHook::compile(
+            r#"
+            fn on_event(kind, event) {
+                event.timestamp = "yesterday";
+                event.slot = 1;
+                if kind == "log" {
+                    event.program_id = "../other";
+                    event.logs = ["Program log: redacted"];
+                } else {
+                    event.pubkey = "other";
+                    event.lamports = 0;
+                }
+                event.team = "risk";
+                event
+            }
+            "#,
+            10_000,
+        )
+        .unwrap();
+
+        let ScriptOutcome::Replace(replaced) = hook.apply(&log_event(&["Program log: a"])).unwrap()
+        else {
+            panic!("expected a replacement");
+        };
+        let Event::Log(event) = *replaced else {
+            panic!("expected a log event");
+        };
+        assert_eq!(event.timestamp, "2024-01-15T10:30:45Z");
+        assert_eq!(event.slot, 12345);
+        assert_eq!(event.program_id, "program123");
+        assert_eq!(event.logs, ["Program log: redacted"]);
+        assert_eq!(event.extra["team"], "risk");
+
+        let account = AccountEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "pubkey123".to_string(),
+            12345,
+            1_000_000,
+            "base64data".to_string(),
+        );
+        let ScriptOutcome::Replace(replaced) = hook.apply(&account.into()).unwrap() else {
+            panic!("expected a replacement");
+        };
+        let Event::Account(event) = *replaced else {
+            panic!("expected an account event");
+        };
+        assert_eq!(event.timestamp, "2024-01-15T10:30:45Z");
+        assert_eq!(event.pubkey, "pubkey123");
+        assert_eq!(event.lamports, 1_000_000);
+        assert_eq!(event.extra["team"], "risk");
+    }
+
+    #[test]
+    fn test_script_requires_hook_function() {
+        assert!(ScriptHook::compile("fn other(event) { event }", 1_000).is_err());
+        assert!(ScriptHook::compile("fn on_event(kind, event) {", 1_000).is_err());
+
+        let hook = ScriptHook::compile("fn on_event(kind, event) { #{ slot: \"x\" } }", 1_000).unwrap();
+        assert!(hook.apply(&log_event(&[])).is_err());
+    }
+}
@@ -1,15 +1,405 @@
-//! JSONL file storage for events
+//! Event sinks and JSONL file storage for events
 
+mod kafka;
+mod nats;
+mod parquet;
+mod postgres;
+mod redis;
+mod s3;
+mod sqlite;
+
+pub use kafka::{KafkaOptions, KafkaSink};
+pub use nats::{NatsOptions, NatsSink};
+pub use self::parquet::{ParquetOptions, ParquetSink};
+pub use postgres::{PostgresOptions, PostgresSink};
+pub use self::redis::{RedisOptions, RedisSink};
+pub use s3::{ClosedSegment, SegmentUploader, UploadOptions};
+pub use sqlite::SqliteSink;
+
+use crate::config::{Compression, Config, SinkKind};
+use crate::event::Envelope;
+use crate::metrics::MetricsRegistry;
+use s3::upload_marker;
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
 use serde::Serialize;
-use std::fs::OpenOptions;
-use std::io::Write;
-use tracing::trace;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{error, info, trace, warn};
+
+/// Events buffered per sink before new ones are dropped for that sink
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Destination for events decoded from a subscription
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// Short name used in logs and metric labels
+    fn name(&self) -> &str;
+
+    /// Persist or forward a single event
+    async fn write_event(&self, envelope: &Envelope) -> Result<()>;
+
+    /// Flush buffered data and release resources on shutdown
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Fan-out over several sinks with per-sink failure isolation
+///
+/// Every sink has its own queue and background worker, so a slow sink only
+/// falls behind itself. A write that fails or exceeds the write timeout, and
+/// an event dropped because the sink's queue is full, are logged and counted
+/// in `sol_sink_errors_total{sink=...}` without affecting the others.
+pub struct SinkSet {
+    queues: Vec<SinkQueue>,
+    metrics: MetricsRegistry,
+}
+
+/// Queue and worker of one sink
+struct SinkQueue {
+    name: String,
+    sender: mpsc::Sender<Arc<Envelope>>,
+    task: JoinHandle<()>,
+}
+
+impl SinkSet {
+    /// Start a worker for each of the already constructed sinks
+    pub fn new(sinks: Vec<Box<dyn EventSink>>, timeout: Duration, metrics: MetricsRegistry) -> Self {
+        let queues = sinks
+            .into_iter()
+            .map(|sink| {
+                let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+                SinkQueue {
+                    name: sink.name().to_string(),
+                    sender,
+                    task: tokio::spawn(drain(sink, receiver, timeout, metrics.clone())),
+                }
+            })
+            .collect();
+        Self { queues, metrics }
+    }
+
+    /// Build the sinks listed in `SINKS`
+    pub async fn from_config(config: &Config, metrics: MetricsRegistry) -> Result<Self> {
+        let mut sinks: Vec<Box<dyn EventSink>> = Vec::new();
+        for kind in &config.sinks {
+            let sink: Box<dyn EventSink> = match kind {
+                SinkKind::Jsonl => {
+                    info!(
+                        "JSONL sink: {} (compression={})",
+                        config.event_log_path,
+                        config.compression.as_str()
+                    );
+                    let mut writer = JsonlWriter::new(&config.event_log_path)
+                        .with_compression(config.compression)
+                        .with_max_segment_bytes(config.segment_max_bytes);
+                    // Repaired first, so segments left by a crash are uploaded intact
+                    writer.recover()?;
+                    if let Some(uploader) = segment_uploader(config, &metrics)? {
+                        writer = writer.with_uploader(uploader);
+                    }
+                    Box::new(writer)
+                }
+                SinkKind::Sqlite => {
+                    info!("SQLite sink: {}", config.sqlite_path);
+                    Box::new(SqliteSink::open(&config.sqlite_path)?)
+                }
+                SinkKind::Postgres => {
+                    let url = config
+                        .postgres_url
+                        .as_ref()
+                        .context("SINKS=postgres requires POSTGRES_URL to be set")?;
+                    let options = PostgresOptions {
+                        batch_size: config.postgres_batch_size.max(1),
+                        flush_interval: Duration::from_millis(config.postgres_flush_interval_ms.max(1)),
+                        max_buffered: config.postgres_max_buffered,
+                    };
+                    Box::new(PostgresSink::connect(url, options).await?)
+                }
+                SinkKind::Parquet => {
+                    info!("Parquet sink: {}", config.parquet_dir);
+                    let options = ParquetOptions {
+                        row_group_rows: config.parquet_row_group_rows.max(1),
+                        flush_interval: Duration::from_secs(config.parquet_flush_interval_secs.max(1)),
+                        row_groups_per_file: config.parquet_row_groups_per_file.max(1),
+                    };
+                    Box::new(ParquetSink::open(&config.parquet_dir, options)?)
+                }
+                SinkKind::Kafka => {
+                    let brokers = config
+                        .kafka_brokers
+                        .clone()
+                        .context("SINKS=kafka requires KAFKA_BROKERS to be set")?;
+                    let options = KafkaOptions {
+                        brokers,
+                        topic: config.kafka_topic.clone(),
+                        acks: config.kafka_acks,
+                        idempotent: config.kafka_idempotent,
+                        message_timeout: Duration::from_millis(config.kafka_message_timeout_ms),
+                    };
+                    Box::new(KafkaSink::new(options, metrics.clone())?)
+                }
+                SinkKind::Nats => {
+                    let url = config
+                        .nats_url
+                        .clone()
+                        .context("SINKS=nats requires NATS_URL to be set")?;
+                    let options = NatsOptions {
+                        url,
+                        subject_prefix: config.nats_subject_prefix.clone(),
+                        jetstream: config.nats_jetstream,
+                        stream: config.nats_stream.clone(),
+                    };
+                    Box::new(NatsSink::connect(options).await?)
+                }
+                SinkKind::Redis => {
+                    let url = config
+                        .redis_url
+                        .clone()
+                        .context("SINKS=redis requires REDIS_URL to be set")?;
+                    let options = RedisOptions {
+                        url,
+                        key_prefix: config.redis_key_prefix.clone(),
+                        max_len: config.redis_stream_maxlen,
+                    };
+                    Box::new(RedisSink::connect(options).await?)
+                }
+            };
+            info!("Sink initialized: {}", kind.as_str());
+            sinks.push(sink);
+        }
+
+        if sinks.is_empty() {
+            anyhow::bail!("SINKS must list at least one sink");
+        }
+
+        Ok(Self::new(
+            sinks,
+            Duration::from_secs(config.sink_timeout_secs),
+            metrics,
+        ))
+    }
+
+    /// Names of the configured sinks
+    pub fn names(&self) -> Vec<&str> {
+        self.queues.iter().map(|queue| queue.name.as_str()).collect()
+    }
+
+    /// Queue an event for every sink; never waits
+    ///
+    /// Only fails when no sink had room for the event.
+    pub async fn write_event(&self, envelope: &Envelope) -> Result<()> {
+        let envelope = Arc::new(envelope.clone());
+        let mut dropped = 0;
+        for queue in &self.queues {
+            if queue.sender.try_send(envelope.clone()).is_err() {
+                warn!("Sink {} is backed up, dropping event", queue.name);
+                self.metrics
+                    .sink_errors_total
+                    .with_label_values(&[&queue.name])
+                    .inc();
+                dropped += 1;
+            }
+        }
+
+        if dropped == self.queues.len() {
+            anyhow::bail!("All {} sinks are backed up", dropped);
+        }
+
+        Ok(())
+    }
+
+    /// Stop accepting events, then wait up to `timeout` for every sink to
+    /// write its queue and close
+    pub async fn close(self, timeout: Duration) {
+        let mut tasks = Vec::new();
+        for queue in self.queues {
+            drop(queue.sender);
+            tasks.push(queue.task);
+        }
+        let drain = futures::future::join_all(tasks);
+        if tokio::time::timeout(timeout, drain).await.is_err() {
+            warn!("Gave up on queued sink writes after {:?}", timeout);
+        }
+    }
+}
+
+/// Write queued events to one sink until its queue is closed, then close it
+async fn drain(
+    sink: Box<dyn EventSink>,
+    mut receiver: mpsc::Receiver<Arc<Envelope>>,
+    timeout: Duration,
+    metrics: MetricsRegistry,
+) {
+    while let Some(envelope) = receiver.recv().await {
+        let result = match tokio::time::timeout(timeout, sink.write_event(&envelope)).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("timed out after {:?}", timeout)),
+        };
+        if let Err(e) = result {
+            error!("Sink {} failed to write event: {:#}", sink.name(), e);
+            metrics.sink_errors_total.with_label_values(&[sink.name()]).inc();
+        }
+    }
+    if let Err(e) = sink.close().await {
+        error!("Failed to close sink {}: {:#}", sink.name(), e);
+    }
+}
+
+/// Build the segment uploader when `S3_BUCKET` is set
+///
+/// Segments an earlier run closed but never uploaded are queued right away.
+fn segment_uploader(
+    config: &Config,
+    metrics: &MetricsRegistry,
+) -> Result<Option<Arc<SegmentUploader>>> {
+    let Some(bucket) = &config.s3_bucket else {
+        return Ok(None);
+    };
+    if config.segment_max_bytes == 0 {
+        anyhow::bail!("S3_BUCKET requires SEGMENT_MAX_BYTES so segments are rotated and closed");
+    }
+
+    let options = UploadOptions {
+        bucket: bucket.clone(),
+        region: config.s3_region.clone(),
+        endpoint: config.s3_endpoint.clone(),
+        key_template: config.s3_key_template.clone(),
+        program_id: config
+            .program_id
+            .clone()
+            .unwrap_or_else(|| config.mode.as_str().to_string()),
+        max_attempts: config.s3_upload_attempts,
+        delete_after_upload: config.s3_delete_after_upload,
+    };
+    let store = options.build_store()?;
+    info!(
+        "Uploading closed segments to s3://{}/{}",
+        bucket, options.key_template
+    );
+
+    let uploader = SegmentUploader::spawn(store, options, metrics.clone());
+    let queued = queue_unuploaded_segments(&uploader, Path::new(&config.event_log_path))?;
+    if queued > 0 {
+        info!("Queued {} segments left unuploaded by an earlier run", queued);
+    }
+
+    Ok(Some(Arc::new(uploader)))
+}
+
+/// Queue every existing rotated segment of `base` without an upload marker
+///
+/// Segments of any compression are included, in case it was changed since
+/// they were written. Returns how many were queued.
+fn queue_unuploaded_segments(uploader: &SegmentUploader, base: &Path) -> Result<usize> {
+    let mut queued = 0;
+    for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+        for path in rotated_segments(base, compression)? {
+            if upload_marker(&path).exists() {
+                continue;
+            }
+            let opened_at = segment_opened_at(&path).unwrap_or_else(OffsetDateTime::now_utc);
+            uploader.enqueue(ClosedSegment { path, opened_at });
+            queued += 1;
+        }
+    }
+    Ok(queued)
+}
 
 /// JSONL file writer for append-only event storage
+///
+/// Without compression or rotation every event is appended and flushed to
+/// `file_path` directly. With compression enabled, events are streamed into
+/// a `.jsonl.gz` or `.jsonl.zst` segment that only becomes a valid archive
+/// once it is closed, either at rotation or via [`JsonlWriter::close`].
+///
+/// Plain files cut short by a crash are repaired by [`JsonlWriter::recover`]
+/// at startup.
 pub struct JsonlWriter {
     /// File path for writing events
     file_path: String,
+    /// Compression applied to segments
+    compression: Compression,
+    /// Uncompressed bytes after which a segment is rotated (0 disables rotation)
+    max_segment_bytes: u64,
+    /// Receives every closed segment for upload to object storage
+    uploader: Option<Arc<SegmentUploader>>,
+    /// Currently open segment and rotation bookkeeping
+    state: Mutex<WriterState>,
+}
+
+/// Mutable state shared across writes
+#[derive(Default)]
+struct WriterState {
+    /// Open segment, created lazily on first write
+    segment: Option<Segment>,
+    /// Index of the next rotated segment
+    next_index: u64,
+}
+
+/// A single output file being written
+struct Segment {
+    path: PathBuf,
+    opened_at: OffsetDateTime,
+    encoder: SegmentEncoder,
+    /// Uncompressed bytes written to this segment
+    bytes_written: u64,
+}
+
+/// Output stream for a segment, optionally wrapped in a compressor
+enum SegmentEncoder {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl SegmentEncoder {
+    fn open(file: File, compression: Compression) -> io::Result<Self> {
+        Ok(match compression {
+            Compression::None => SegmentEncoder::Plain(file),
+            Compression::Gzip => {
+                SegmentEncoder::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Compression::Zstd => SegmentEncoder::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    /// Write the compression trailer and flush the underlying file
+    fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            SegmentEncoder::Plain(file) => file,
+            SegmentEncoder::Gzip(encoder) => encoder.finish()?,
+            SegmentEncoder::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        file.sync_all()
+    }
+}
+
+impl Write for SegmentEncoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            SegmentEncoder::Plain(file) => file.write(buf),
+            SegmentEncoder::Gzip(encoder) => encoder.write(buf),
+            SegmentEncoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            SegmentEncoder::Plain(file) => file.flush(),
+            SegmentEncoder::Gzip(encoder) => encoder.flush(),
+            SegmentEncoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
 }
 
 impl JsonlWriter {
@@ -17,51 +407,424 @@ impl JsonlWriter {
     pub fn new(file_path: impl AsRef<str>) -> Self {
         Self {
             file_path: file_path.as_ref().to_string(),
+            compression: Compression::None,
+            max_segment_bytes: 0,
+            uploader: None,
+            state: Mutex::new(WriterState::default()),
         }
     }
 
+    /// Set the compression used for segments
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Rotate segments after `max_bytes` of uncompressed output (0 disables rotation)
+    pub fn with_max_segment_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_segment_bytes = max_bytes;
+        self
+    }
+
+    /// Hand closed segments to `uploader`
+    pub fn with_uploader(mut self, uploader: Arc<SegmentUploader>) -> Self {
+        self.uploader = Some(uploader);
+        self
+    }
+
     /// Write an event to the JSONL file (appends to file)
     pub async fn write<T: Serialize>(&self, event: &T) -> Result<()> {
         // Serialize event to JSON
-        let json = serde_json::to_string(event)
+        let mut line = serde_json::to_string(event)
             .context("Failed to serialize event to JSON")?;
+        line.push('\n');
 
-        // Append to file (create if it doesn't exist)
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.file_path)
-            .with_context(|| format!("Failed to open file for writing: {}", self.file_path))?;
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("JSONL writer lock poisoned"))?;
 
-        // Write JSON line with newline
-        let mut writer = file;
-        writer
-            .write_all(json.as_bytes())
-            .with_context(|| format!("Failed to write to file: {}", self.file_path))?;
-        writer.write_all(b"\n")?;
-        
-        // Flush to ensure data is written
-        writer.flush()?;
+        if state.segment.is_none() {
+            let segment = self.open_segment(&mut state)?;
+            state.segment = Some(segment);
+        }
+        let segment = state.segment.as_mut().expect("segment opened above");
 
-        trace!("Wrote event to {}: {} bytes", self.file_path, json.len());
+        segment
+            .encoder
+            .write_all(line.as_bytes())
+            .with_context(|| format!("Failed to write to file: {}", segment.path.display()))?;
+        segment.bytes_written += line.len() as u64;
+
+        // Plain files are flushed per event; compressed segments are only
+        // guaranteed complete once closed
+        if let SegmentEncoder::Plain(file) = &mut segment.encoder {
+            file.flush()?;
+        }
+
+        trace!("Wrote event to {}: {} bytes", segment.path.display(), line.len());
+
+        if self.max_segment_bytes > 0 && segment.bytes_written >= self.max_segment_bytes {
+            if let Some(segment) = state.segment.take() {
+                self.finish_segment(segment)?;
+            }
+        }
 
         Ok(())
     }
 
+    /// Close the open segment, finalizing any compressed stream
+    ///
+    /// Returns the path of the closed segment, if one was open.
+    pub fn close(&self) -> Result<Option<PathBuf>> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("JSONL writer lock poisoned"))?;
+
+        match state.segment.take() {
+            Some(segment) => self.finish_segment(segment).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Repair plain files an earlier run left with an incomplete last record
+    ///
+    /// Call once at startup, before the first write. This checks `file_path`,
+    /// or every rotated segment of it when rotation is enabled. Compressed
+    /// segments are not covered: a gzip or zstd stream cut off by a crash
+    /// lacks its trailer and can't be fixed by truncating to a line, so such
+    /// segments are left as they are.
+    pub fn recover(&self) -> Result<()> {
+        if self.compression != Compression::None {
+            return Ok(());
+        }
+
+        let paths = if self.max_segment_bytes > 0 {
+            rotated_segments(Path::new(&self.file_path), self.compression)?
+        } else {
+            vec![PathBuf::from(&self.file_path)]
+        };
+        for path in paths.into_iter().filter(|path| path.exists()) {
+            match repair_tail(&path)? {
+                TailRepair::Intact => {}
+                TailRepair::NewlineAdded => warn!(
+                    "{} ended without a trailing newline; terminated the final record",
+                    path.display()
+                ),
+                TailRepair::Truncated { bytes, quarantine } => warn!(
+                    "{} ended with an incomplete record; moved {} bytes to {}",
+                    path.display(),
+                    bytes,
+                    quarantine.display()
+                ),
+            }
+        }
+        Ok(())
+    }
+
     /// Get the file path
     #[allow(dead_code)]
     pub fn file_path(&self) -> &str {
         &self.file_path
     }
+
+    /// Open the next segment for appending
+    fn open_segment(&self, state: &mut WriterState) -> Result<Segment> {
+        let opened_at = OffsetDateTime::now_utc();
+        let path = if self.max_segment_bytes > 0 {
+            let index = state.next_index;
+            state.next_index += 1;
+            rotated_segment_path(
+                Path::new(&self.file_path),
+                self.compression,
+                opened_at,
+                index,
+            )
+        } else {
+            PathBuf::from(format!("{}{}", self.file_path, self.compression.extension()))
+        };
+
+        // Append to file (create if it doesn't exist); concatenated gzip
+        // members and zstd frames are both valid archives
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open file for writing: {}", path.display()))?;
+
+        let encoder = SegmentEncoder::open(file, self.compression)
+            .with_context(|| format!("Failed to initialize compression for {}", path.display()))?;
+
+        if self.compression != Compression::None || self.max_segment_bytes > 0 {
+            info!("Opened segment {}", path.display());
+        }
+
+        Ok(Segment {
+            path,
+            opened_at,
+            encoder,
+            bytes_written: 0,
+        })
+    }
+
+    /// Finalize a segment, queue it for upload, and return its path
+    fn finish_segment(&self, segment: Segment) -> Result<PathBuf> {
+        let Segment {
+            path,
+            opened_at,
+            encoder,
+            bytes_written,
+        } = segment;
+
+        encoder
+            .finish()
+            .with_context(|| format!("Failed to finalize segment: {}", path.display()))?;
+
+        info!("Closed segment {} ({} bytes uncompressed)", path.display(), bytes_written);
+
+        if let Some(uploader) = &self.uploader {
+            uploader.enqueue(ClosedSegment {
+                path: path.clone(),
+                opened_at,
+            });
+        }
+
+        Ok(path)
+    }
+}
+
+#[async_trait]
+impl EventSink for JsonlWriter {
+    fn name(&self) -> &str {
+        "jsonl"
+    }
+
+    async fn write_event(&self, envelope: &Envelope) -> Result<()> {
+        self.write(envelope).await
+    }
+
+    /// Close the open segment and wait for pending uploads
+    async fn close(&self) -> Result<()> {
+        let result = JsonlWriter::close(self).map(|_| ());
+        if let Some(uploader) = &self.uploader {
+            uploader.shutdown().await;
+        }
+        result
+    }
+}
+
+impl Drop for JsonlWriter {
+    fn drop(&mut self) {
+        // Best effort so compressed segments are not left without a trailer
+        let segment = self
+            .state
+            .get_mut()
+            .ok()
+            .and_then(|state| state.segment.take());
+        if let Some(segment) = segment {
+            let _ = self.finish_segment(segment);
+        }
+    }
+}
+
+/// Outcome of checking the end of an existing plain JSONL file
+#[derive(Debug, PartialEq, Eq)]
+enum TailRepair {
+    /// The file is empty or ends with a complete line
+    Intact,
+    /// The final record was valid JSON but missing its newline
+    NewlineAdded,
+    /// An incomplete final record was cut off and saved to `quarantine`
+    Truncated { bytes: u64, quarantine: PathBuf },
+}
+
+/// Make sure a plain JSONL file ends with a complete record
+///
+/// Records are written as one `line + '\n'`, so a file that does not end in
+/// a newline was interrupted mid-write. The unterminated tail is kept if it
+/// parses as JSON; otherwise it is appended to `<path>.partial` and the file
+/// is truncated back to the last complete line.
+fn repair_tail(path: &Path) -> Result<TailRepair> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {} for recovery", path.display()))?;
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(TailRepair::Intact);
+    }
+
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::Start(len - 1))?;
+    file.read_exact(&mut last)?;
+    if last[0] == b'\n' {
+        return Ok(TailRepair::Intact);
+    }
+
+    // Scan backwards for the newline ending the last complete record
+    let mut start = 0;
+    let mut end = len;
+    let mut chunk = vec![0u8; 8192];
+    while end > 0 {
+        let size = chunk.len().min(end as usize);
+        let offset = end - size as u64;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut chunk[..size])?;
+        if let Some(pos) = chunk[..size].iter().rposition(|&b| b == b'\n') {
+            start = offset + pos as u64 + 1;
+            break;
+        }
+        end = offset;
+    }
+
+    let mut tail = Vec::with_capacity((len - start) as usize);
+    file.seek(SeekFrom::Start(start))?;
+    file.read_to_end(&mut tail)?;
+
+    if serde_json::from_slice::<serde_json::Value>(&tail).is_ok() {
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(b"\n")?;
+        file.sync_all()?;
+        return Ok(TailRepair::NewlineAdded);
+    }
+
+    let mut quarantine = path.as_os_str().to_owned();
+    quarantine.push(".partial");
+    let quarantine = PathBuf::from(quarantine);
+    let mut partial = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&quarantine)
+        .with_context(|| format!("Failed to open {}", quarantine.display()))?;
+    partial.write_all(&tail)?;
+    partial.write_all(b"\n")?;
+    partial.sync_all()?;
+
+    file.set_len(start)
+        .with_context(|| format!("Failed to truncate {}", path.display()))?;
+    file.sync_all()?;
+
+    Ok(TailRepair::Truncated {
+        bytes: tail.len() as u64,
+        quarantine,
+    })
+}
+
+/// JSON text of a map stored in its own column, or `None` if it is empty
+fn object_column(map: &impl Serialize) -> serde_json::Result<Option<String>> {
+    let json = serde_json::to_string(map)?;
+    Ok((json != "{}").then_some(json))
+}
+
+/// Build the path of a rotated segment
+///
+/// `./events.jsonl` becomes `./events-20240115T103045Z-0003.jsonl.zst`.
+fn rotated_segment_path(
+    base: &Path,
+    compression: Compression,
+    opened_at: OffsetDateTime,
+    index: u64,
+) -> PathBuf {
+    let file_name = base
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "events.jsonl".to_string());
+    let stem = file_name.strip_suffix(".jsonl").unwrap_or(&file_name);
+
+    let stamp = format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        opened_at.year(),
+        u8::from(opened_at.month()),
+        opened_at.day(),
+        opened_at.hour(),
+        opened_at.minute(),
+        opened_at.second()
+    );
+
+    base.with_file_name(format!(
+        "{}-{}-{:04}.jsonl{}",
+        stem,
+        stamp,
+        index,
+        compression.extension()
+    ))
+}
+
+/// Existing rotated segments of `base` with `compression`, oldest first
+///
+/// Matches the names built by [`rotated_segment_path`], e.g.
+/// `events-20240115T103045Z-0003.jsonl.zst` for `./events.jsonl`.
+fn rotated_segments(base: &Path, compression: Compression) -> Result<Vec<PathBuf>> {
+    let dir = match base.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = base
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "events.jsonl".to_string());
+    let prefix = format!("{}-", file_name.strip_suffix(".jsonl").unwrap_or(&file_name));
+    let suffix = format!(".jsonl{}", compression.extension());
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to list {}", dir.display()));
+        }
+    };
+    let mut segments = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        // `<stamp>-<index>`, e.g. `20240115T103045Z-0003`
+        let is_segment = name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(&suffix))
+            .is_some_and(|rest| rest.len() >= 21 && rest.as_bytes()[15..17] == *b"Z-");
+        if is_segment && path.is_file() {
+            segments.push(path);
+        }
+    }
+    // Timestamps and zero-padded indexes sort in the order segments were opened
+    segments.sort();
+    Ok(segments)
+}
+
+/// When a rotated segment was opened, from the timestamp in its name
+fn segment_opened_at(path: &Path) -> Option<OffsetDateTime> {
+    let name = path.file_name()?.to_str()?;
+    // `<stem>-<YYYYMMDD>T<HHMMSS>Z-<index>.jsonl...`
+    let (stem, _) = name.split_once(".jsonl")?;
+    let (rest, _index) = stem.rsplit_once('-')?;
+    let (_, stamp) = rest.rsplit_once('-')?;
+    let field = |range: std::ops::Range<usize>| stamp.get(range)?.parse::<u32>().ok();
+
+    let date = time::Date::from_calendar_date(
+        field(0..4)? as i32,
+        time::Month::try_from(field(4..6)? as u8).ok()?,
+        field(6..8)? as u8,
+    )
+    .ok()?;
+    let time = time::Time::from_hms(field(9..11)? as u8, field(11..13)? as u8, field(13..15)? as u8)
+        .ok()?;
+    Some(date.with_time(time).assume_utc())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures::TryStreamExt;
+    use object_store::memory::InMemory;
+    use object_store::ObjectStore;
     use serde::{Deserialize, Serialize};
     use tempfile::NamedTempFile;
     use std::fs;
-    use std::io::{BufRead, BufReader};
+    use std::io::{BufRead, BufReader, Read};
 
     #[derive(Serialize, Deserialize)]
     struct TestEvent {
@@ -150,4 +913,300 @@ mod tests {
         assert_eq!(deserialized.id, 100);
         assert_eq!(deserialized.message.len(), 10000);
     }
+
+    /// List rotated segments in `dir`, sorted by name
+    fn segments_in(dir: &Path, extension: &str) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().ends_with(extension))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn test_gzip_segments_rotate_and_decode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+
+        // Each event is ~30 bytes, so every second write closes a segment
+        let writer = JsonlWriter::new(path.to_str().unwrap())
+            .with_compression(Compression::Gzip)
+            .with_max_segment_bytes(40);
+
+        for id in 0..5 {
+            let event = TestEvent {
+                id,
+                message: "rotate".to_string(),
+            };
+            writer.write(&event).await.unwrap();
+        }
+        let last = writer.close().unwrap();
+        assert!(last.is_some());
+
+        let segments = segments_in(dir.path(), ".jsonl.gz");
+        assert_eq!(segments.len(), 3);
+
+        let mut ids = Vec::new();
+        for segment in &segments {
+            // Every closed segment must decode on its own
+            let mut decoded = String::new();
+            flate2::read::GzDecoder::new(fs::File::open(segment).unwrap())
+                .read_to_string(&mut decoded)
+                .unwrap();
+            for line in decoded.lines() {
+                let event: TestEvent = serde_json::from_str(line).unwrap();
+                ids.push(event.id);
+            }
+        }
+        assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_zstd_single_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+
+        let writer = JsonlWriter::new(path.to_str().unwrap()).with_compression(Compression::Zstd);
+
+        let event = TestEvent {
+            id: 7,
+            message: "compressed".to_string(),
+        };
+        writer.write(&event).await.unwrap();
+        let closed = writer.close().unwrap().unwrap();
+        assert_eq!(closed, dir.path().join("events.jsonl.zst"));
+
+        let decoded = zstd::decode_all(fs::File::open(&closed).unwrap()).unwrap();
+        let deserialized: TestEvent =
+            serde_json::from_str(String::from_utf8(decoded).unwrap().trim()).unwrap();
+        assert_eq!(deserialized.id, 7);
+        assert_eq!(deserialized.message, "compressed");
+    }
+
+    #[test]
+    fn test_rotated_segment_path() {
+        // 2024-01-15T10:30:45Z
+        let opened_at = OffsetDateTime::from_unix_timestamp(1_705_314_645).unwrap();
+        let path = rotated_segment_path(Path::new("./data/events.jsonl"), Compression::Zstd, opened_at, 3);
+        assert_eq!(path, PathBuf::from("./data/events-20240115T103045Z-0003.jsonl.zst"));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_tail_is_quarantined_on_startup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        fs::write(&path, "{\"id\":1,\"message\":\"ok\"}\n{\"id\":2,\"mess").unwrap();
+
+        let writer = JsonlWriter::new(path.to_str().unwrap());
+        writer.recover().unwrap();
+        writer
+            .write(&TestEvent { id: 3, message: "after restart".to_string() })
+            .await
+            .unwrap();
+
+        let lines: Vec<String> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            serde_json::from_str::<TestEvent>(line).unwrap();
+        }
+
+        let partial = fs::read_to_string(dir.path().join("events.jsonl.partial")).unwrap();
+        assert_eq!(partial, "{\"id\":2,\"mess\n");
+    }
+
+    #[test]
+    fn test_recover_repairs_rotated_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("events.jsonl");
+        let torn = dir.path().join("events-20240115T103045Z-0001.jsonl");
+        let intact = dir.path().join("events-20240115T103045Z-0000.jsonl");
+        fs::write(&intact, "{\"id\":1}\n").unwrap();
+        fs::write(&torn, "{\"id\":2}\n{\"id\":3,\"mess").unwrap();
+        fs::write(dir.path().join("events-notes.jsonl"), "{\"id\":").unwrap();
+
+        assert_eq!(
+            rotated_segments(&base, Compression::None).unwrap(),
+            vec![intact.clone(), torn.clone()]
+        );
+
+        JsonlWriter::new(base.to_str().unwrap())
+            .with_max_segment_bytes(1024)
+            .recover()
+            .unwrap();
+        assert_eq!(fs::read_to_string(&intact).unwrap(), "{\"id\":1}\n");
+        assert_eq!(fs::read_to_string(&torn).unwrap(), "{\"id\":2}\n");
+        // Files that aren't segments of this writer are left alone
+        assert_eq!(fs::read_to_string(dir.path().join("events-notes.jsonl")).unwrap(), "{\"id\":");
+    }
+
+    #[tokio::test]
+    async fn test_unuploaded_segments_are_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("events.jsonl");
+        let pending = dir.path().join("events-20240115T103045Z-0000.jsonl.zst");
+        let uploaded = dir.path().join("events-20240115T103045Z-0001.jsonl.zst");
+        let plain = dir.path().join("events-20240116T000000Z-0000.jsonl");
+        for path in [&pending, &uploaded, &plain] {
+            fs::write(path, b"segment").unwrap();
+        }
+        fs::write(upload_marker(&uploaded), b"").unwrap();
+
+        let store = Arc::new(InMemory::new());
+        let options = UploadOptions {
+            bucket: "events".to_string(),
+            region: "us-east-1".to_string(),
+            endpoint: None,
+            key_template: "{date}/{filename}".to_string(),
+            program_id: "program123".to_string(),
+            max_attempts: 1,
+            delete_after_upload: false,
+        };
+        let uploader = SegmentUploader::spawn(store.clone(), options, MetricsRegistry::default());
+        assert_eq!(queue_unuploaded_segments(&uploader, &base).unwrap(), 2);
+        uploader.shutdown().await;
+
+        let mut keys: Vec<String> = store
+            .list(None)
+            .map_ok(|meta| meta.location.to_string())
+            .try_collect()
+            .await
+            .unwrap();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "2024-01-15/events-20240115T103045Z-0000.jsonl.zst",
+                "2024-01-16/events-20240116T000000Z-0000.jsonl",
+            ]
+        );
+        assert!(upload_marker(&pending).exists());
+    }
+
+    #[test]
+    fn test_repair_tail_terminates_complete_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+
+        fs::write(&path, "{\"id\":1}\n").unwrap();
+        assert_eq!(repair_tail(&path).unwrap(), TailRepair::Intact);
+
+        fs::write(&path, "{\"id\":1}\n{\"id\":2}").unwrap();
+        assert_eq!(repair_tail(&path).unwrap(), TailRepair::NewlineAdded);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"id\":1}\n{\"id\":2}\n");
+        assert!(!dir.path().join("events.jsonl.partial").exists());
+    }
+
+    /// Sink that always fails, for isolation tests
+    struct FailingSink;
+
+    #[async_trait]
+    impl EventSink for FailingSink {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        async fn write_event(&self, _envelope: &Envelope) -> Result<()> {
+            anyhow::bail!("sink unavailable")
+        }
+    }
+
+    /// Sink whose writes never complete
+    struct HangingSink;
+
+    #[async_trait]
+    impl EventSink for HangingSink {
+        fn name(&self) -> &str {
+            "hanging"
+        }
+
+        async fn write_event(&self, _envelope: &Envelope) -> Result<()> {
+            std::future::pending().await
+        }
+    }
+
+    fn sample_event() -> Envelope {
+        Envelope::test(crate::event::LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "signature123".to_string(),
+            12345,
+            "program123".to_string(),
+            vec!["Program log: hello".to_string()],
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_sink_set_isolates_failures() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path().to_str().unwrap();
+        let metrics = MetricsRegistry::default();
+
+        let sinks = SinkSet::new(
+            vec![Box::new(FailingSink), Box::new(JsonlWriter::new(path))],
+            Duration::from_secs(1),
+            metrics.clone(),
+        );
+        assert_eq!(sinks.names(), vec!["failing", "jsonl"]);
+
+        sinks.write_event(&sample_event()).await.unwrap();
+        sinks.close(Duration::from_secs(5)).await;
+
+        let content = fs::read_to_string(path).unwrap();
+        assert!(content.contains("signature123"));
+        assert_eq!(
+            metrics.sink_errors_total.with_label_values(&["failing"]).get(),
+            1.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sink_set_does_not_wait_for_slow_sinks() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path().to_str().unwrap();
+
+        let sinks = SinkSet::new(
+            vec![Box::new(HangingSink), Box::new(JsonlWriter::new(path))],
+            Duration::from_secs(3600),
+            MetricsRegistry::default(),
+        );
+        for _ in 0..3 {
+            tokio::time::timeout(Duration::from_secs(1), sinks.write_event(&sample_event()))
+                .await
+                .expect("write waited for the hanging sink")
+                .unwrap();
+        }
+        sinks.close(Duration::from_millis(200)).await;
+
+        let content = fs::read_to_string(path).unwrap();
+        assert_eq!(content.lines().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_sink_set_fails_when_all_sinks_are_backed_up() {
+        let metrics = MetricsRegistry::default();
+        let sinks = SinkSet::new(
+            vec![Box::new(HangingSink)],
+            Duration::from_secs(3600),
+            metrics.clone(),
+        );
+
+        // The worker holds one event and the queue the next QUEUE_CAPACITY
+        let mut accepted = 0;
+        while sinks.write_event(&sample_event()).await.is_ok() {
+            accepted += 1;
+            assert!(accepted <= QUEUE_CAPACITY + 1, "queue is unbounded");
+            tokio::task::yield_now().await;
+        }
+        assert!(accepted >= QUEUE_CAPACITY);
+        assert_eq!(
+            metrics.sink_errors_total.with_label_values(&["hanging"]).get(),
+            1.0
+        );
+        sinks.close(Duration::from_millis(10)).await;
+    }
 }
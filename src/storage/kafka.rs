@@ -0,0 +1,201 @@
+//! Kafka event sink
+//!
+//! Each event is published as JSON to a single topic, keyed by program id
+//! (log events) or pubkey (account events) so all events for one entity land
+//! in the same partition and keep their order. Delivery reports are awaited
+//! in the background; failures are counted in
+//! `sol_sink_delivery_failures_total{sink="kafka"}`.
+
+use super::EventSink;
+use crate::config::KafkaAcks;
+use crate::event::Envelope;
+use crate::metrics::MetricsRegistry;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use rdkafka::util::Timeout;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// How long `close` waits for in-flight messages
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Producer options for the Kafka sink
+#[derive(Debug, Clone)]
+pub struct KafkaOptions {
+    /// Comma-separated bootstrap servers
+    pub brokers: String,
+    /// Topic events are published to
+    pub topic: String,
+    /// Broker acknowledgements required per message
+    pub acks: KafkaAcks,
+    /// Enable the idempotent producer (requires `acks=all`)
+    pub idempotent: bool,
+    /// Upper bound on time spent delivering a message, including retries
+    pub message_timeout: Duration,
+}
+
+impl KafkaOptions {
+    /// librdkafka client configuration for these options
+    fn client_config(&self) -> Result<ClientConfig> {
+        if self.idempotent && self.acks != KafkaAcks::All {
+            anyhow::bail!(
+                "KAFKA_IDEMPOTENT requires KAFKA_ACKS=all (got {})",
+                self.acks.as_str()
+            );
+        }
+
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &self.brokers)
+            .set("acks", self.acks.as_str())
+            .set("enable.idempotence", self.idempotent.to_string())
+            .set(
+                "message.timeout.ms",
+                self.message_timeout.as_millis().to_string(),
+            );
+        Ok(config)
+    }
+}
+
+/// Event sink publishing to a Kafka topic
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+    metrics: MetricsRegistry,
+}
+
+impl KafkaSink {
+    /// Create the producer; connections are established lazily by librdkafka
+    pub fn new(options: KafkaOptions, metrics: MetricsRegistry) -> Result<Self> {
+        let producer: FutureProducer = options
+            .client_config()?
+            .create()
+            .context("Failed to create Kafka producer")?;
+
+        info!(
+            "Kafka producer for topic {} (acks={}, idempotent={})",
+            options.topic,
+            options.acks.as_str(),
+            options.idempotent
+        );
+
+        Ok(Self {
+            producer,
+            topic: options.topic,
+            metrics,
+        })
+    }
+}
+
+#[async_trait]
+impl EventSink for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    /// Enqueue the event; delivery is confirmed in the background
+    async fn write_event(&self, envelope: &Envelope) -> Result<()> {
+        let payload = serde_json::to_vec(envelope).context("Failed to serialize event to JSON")?;
+        let record = FutureRecord::to(&self.topic)
+            .key(envelope.event.entity_key())
+            .payload(&payload);
+
+        let delivery = self
+            .producer
+            .send_result(record)
+            .map_err(|(e, _)| anyhow::anyhow!("Failed to enqueue Kafka message: {}", e))?;
+
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            let failure = match delivery.await {
+                Ok(Ok(_)) => return,
+                Ok(Err((e, _))) => e.to_string(),
+                Err(_) => "delivery report dropped".to_string(),
+            };
+            error!("Kafka delivery failed: {}", failure);
+            metrics
+                .sink_delivery_failures_total
+                .with_label_values(&["kafka"])
+                .inc();
+        });
+
+        Ok(())
+    }
+
+    /// Wait for in-flight messages to be delivered
+    async fn close(&self) -> Result<()> {
+        let producer = self.producer.clone();
+        tokio::task::spawn_blocking(move || producer.flush(Timeout::After(FLUSH_TIMEOUT)))
+            .await
+            .context("Kafka flush task panicked")?
+            .context("Failed to flush Kafka producer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Envelope, LogEvent};
+
+    fn options(acks: KafkaAcks, idempotent: bool) -> KafkaOptions {
+        KafkaOptions {
+            brokers: "localhost:9092".to_string(),
+            topic: "solana-events".to_string(),
+            acks,
+            idempotent,
+            message_timeout: Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn test_client_config() {
+        let config = options(KafkaAcks::All, true).client_config().unwrap();
+        assert_eq!(config.get("acks"), Some("all"));
+        assert_eq!(config.get("enable.idempotence"), Some("true"));
+        assert_eq!(config.get("message.timeout.ms"), Some("30000"));
+    }
+
+    #[test]
+    fn test_idempotence_requires_acks_all() {
+        assert!(options(KafkaAcks::Leader, true).client_config().is_err());
+        assert!(options(KafkaAcks::Leader, false).client_config().is_ok());
+    }
+
+    /// Requires a broker, e.g. `docker run -p 9092:9092 redpandadata/redpanda`
+    #[tokio::test]
+    #[ignore = "requires a local Kafka broker (KAFKA_TEST_BROKERS)"]
+    async fn test_publish_to_local_broker() {
+        let brokers = std::env::var("KAFKA_TEST_BROKERS").expect("KAFKA_TEST_BROKERS not set");
+        let metrics = MetricsRegistry::default();
+        let sink = KafkaSink::new(
+            KafkaOptions {
+                brokers,
+                ..options(KafkaAcks::All, true)
+            },
+            metrics.clone(),
+        )
+        .unwrap();
+
+        let event = Envelope::test(LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "signature123".to_string(),
+            12345,
+            "program123".to_string(),
+            vec!["Program log: hello".to_string()],
+        ));
+        sink.write_event(&event).await.unwrap();
+        sink.close().await.unwrap();
+        // Let the background delivery task observe the report
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(
+            metrics
+                .sink_delivery_failures_total
+                .with_label_values(&["kafka"])
+                .get(),
+            0.0
+        );
+    }
+}
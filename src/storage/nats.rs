@@ -0,0 +1,230 @@
+//! NATS / JetStream event sink
+//!
+//! Events are published as JSON to subjects derived from the event:
+//! `{prefix}.logs.<program_id>` and `{prefix}.account.<pubkey>`, so consumers
+//! can subscribe to exactly the programs or accounts they care about. With
+//! JetStream enabled each publish waits for the stream's acknowledgement and
+//! is retried on failure, giving at-least-once delivery.
+
+use super::EventSink;
+use crate::event::{redact_endpoint, Envelope, Event};
+use anyhow::{Context, Result};
+use async_nats::jetstream;
+use async_trait::async_trait;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Publish attempts per event before giving up in JetStream mode
+const JETSTREAM_ATTEMPTS: u32 = 3;
+
+/// Connection options for the NATS sink
+#[derive(Debug, Clone)]
+pub struct NatsOptions {
+    /// Server URL, e.g. `nats://localhost:4222`
+    pub url: String,
+    /// First subject token, e.g. `solana`
+    pub subject_prefix: String,
+    /// Wait for JetStream acknowledgements
+    pub jetstream: bool,
+    /// Stream to create (if missing) covering `{prefix}.>`
+    pub stream: Option<String>,
+}
+
+/// How events are published
+enum Publisher {
+    /// Core NATS, fire-and-forget
+    Core(async_nats::Client),
+    /// JetStream with acknowledgements
+    JetStream {
+        client: async_nats::Client,
+        context: jetstream::Context,
+    },
+}
+
+/// Event sink publishing to NATS subjects
+pub struct NatsSink {
+    publisher: Publisher,
+    subject_prefix: String,
+}
+
+impl NatsSink {
+    /// Connect and, in JetStream mode, make sure the stream exists
+    pub async fn connect(options: NatsOptions) -> Result<Self> {
+        // The URL may carry credentials, so only its scheme and host are logged
+        let endpoint = redact_endpoint(&options.url);
+        let client = async_nats::connect(&options.url)
+            .await
+            .with_context(|| format!("Failed to connect to NATS at {}", endpoint))?;
+
+        let publisher = if options.jetstream {
+            let context = jetstream::new(client.clone());
+            if let Some(stream) = &options.stream {
+                context
+                    .get_or_create_stream(jetstream::stream::Config {
+                        name: stream.clone(),
+                        subjects: vec![format!("{}.>", options.subject_prefix)],
+                        ..Default::default()
+                    })
+                    .await
+                    .with_context(|| format!("Failed to create JetStream stream {}", stream))?;
+                info!("Using JetStream stream {}", stream);
+            }
+            Publisher::JetStream { client, context }
+        } else {
+            Publisher::Core(client)
+        };
+
+        info!(
+            "Connected to NATS at {} (jetstream={})",
+            endpoint, options.jetstream
+        );
+
+        Ok(Self {
+            publisher,
+            subject_prefix: options.subject_prefix,
+        })
+    }
+}
+
+#[async_trait]
+impl EventSink for NatsSink {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    async fn write_event(&self, envelope: &Envelope) -> Result<()> {
+        let subject = subject_for(&self.subject_prefix, &envelope.event);
+        let payload = serde_json::to_vec(envelope).context("Failed to serialize event to JSON")?;
+
+        match &self.publisher {
+            Publisher::Core(client) => client
+                .publish(subject, payload.into())
+                .await
+                .context("Failed to publish to NATS"),
+            Publisher::JetStream { context, .. } => {
+                let mut attempt = 1;
+                loop {
+                    let result = async {
+                        context
+                            .publish(subject.clone(), payload.clone().into())
+                            .await?
+                            .await
+                    }
+                    .await;
+
+                    match result {
+                        Ok(_) => return Ok(()),
+                        Err(e) if attempt < JETSTREAM_ATTEMPTS => {
+                            warn!(
+                                "JetStream publish to {} failed (attempt {}): {}",
+                                subject, attempt, e
+                            );
+                            tokio::time::sleep(Duration::from_millis(100 * u64::from(attempt)))
+                                .await;
+                            attempt += 1;
+                        }
+                        Err(e) => {
+                            return Err(anyhow::Error::new(e).context(format!(
+                                "JetStream publish to {} not acknowledged",
+                                subject
+                            )))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flush buffered publishes to the server
+    async fn close(&self) -> Result<()> {
+        let client = match &self.publisher {
+            Publisher::Core(client) => client,
+            Publisher::JetStream { client, .. } => client,
+        };
+        client.flush().await.context("Failed to flush NATS client")
+    }
+}
+
+/// Subject for an event: `{prefix}.logs.<program_id>` or `{prefix}.account.<pubkey>`
+///
+/// Commitment events share the subject of the event they follow up on.
+fn subject_for(prefix: &str, event: &Event) -> String {
+    format!(
+        "{}.{}.{}",
+        prefix,
+        event.subscription_kind(),
+        subject_token(event.entity_key())
+    )
+}
+
+/// Make a value safe to use as a single subject token
+fn subject_token(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '.' | '*' | '>' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{AccountEvent, LogEvent};
+
+    #[test]
+    fn test_subject_for_events() {
+        let log = Event::from(LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "signature123".to_string(),
+            12345,
+            "ComputeBudget111111111111111111111111111111".to_string(),
+            vec![],
+        ));
+        assert_eq!(
+            subject_for("solana", &log),
+            "solana.logs.ComputeBudget111111111111111111111111111111"
+        );
+
+        let account = Event::from(AccountEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "pubkey123".to_string(),
+            12345,
+            1000000,
+            "base64data".to_string(),
+        ));
+        assert_eq!(subject_for("solana", &account), "solana.account.pubkey123");
+    }
+
+    #[test]
+    fn test_subject_token_escapes_wildcards() {
+        assert_eq!(subject_token("a.b*c>d e"), "a_b_c_d_e");
+    }
+
+    /// Requires a server, e.g. `nats-server -js`
+    #[tokio::test]
+    #[ignore = "requires a local nats-server with JetStream (NATS_TEST_URL)"]
+    async fn test_jetstream_publish_against_local_server() {
+        let url = std::env::var("NATS_TEST_URL").expect("NATS_TEST_URL not set");
+        let sink = NatsSink::connect(NatsOptions {
+            url,
+            subject_prefix: "solana-test".to_string(),
+            jetstream: true,
+            stream: Some("SOLANA_TEST".to_string()),
+        })
+        .await
+        .unwrap();
+
+        let event = Envelope::test(LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "signature123".to_string(),
+            12345,
+            "program123".to_string(),
+            vec!["Program log: hello".to_string()],
+        ));
+        sink.write_event(&event).await.unwrap();
+        sink.close().await.unwrap();
+    }
+}
@@ -0,0 +1,1079 @@
+//! Parquet event sink
+//!
+//! Events are written to Hive-style partitions that DuckDB and Spark can
+//! prune on:
+//!
+//! ```text
+//! {dir}/log_events/date=2024-01-15/program_id=<id>/part-20240115T103045Z-0000.parquet
+//! {dir}/account_events/date=2024-01-15/part-20240115T103045Z-0001.parquet
+//! {dir}/commitment_events/date=2024-01-15/part-20240115T103045Z-0002.parquet
+//! ```
+//!
+//! Buffered rows are flushed as a row group once `row_group_rows` is reached
+//! or `flush_interval` elapses. Files are written with an `.inprogress`
+//! suffix and renamed once their footer is written, so readers globbing
+//! `*.parquet` only ever see complete files. File names never reuse an
+//! existing name, so a restart within the same second starts a new file
+//! instead of truncating one.
+//!
+//! Partition values are sanitized: dates that are not `YYYY-MM-DD` land in
+//! `date=unknown`, and program id characters outside the base58 alphabet are
+//! percent-escaped. Events whose timestamps cannot be parsed are rejected by
+//! `write_event` rather than left to fail the row group they would join.
+//!
+//! Log events carry the error of failed transactions in the nullable `err`
+//! (JSON) and `err_name` columns. Log and account events keep the fields added
+//! by a script hook and decoder plugin output in nullable `extra` and
+//! `decoded` JSON columns. Every table ends with the envelope's `received_at`,
+//! (nullable) `block_time`, `seq`, `schema_version`, `endpoint`, `commitment`
+//! and `subscription` columns; `commitment_events` has its own `commitment`,
+//! so there the subscription's level is `subscription_commitment`.
+
+use super::{object_column, EventSink};
+use crate::event::{AccountEvent, CommitmentEvent, Envelope, Event, LogEvent};
+use anyhow::{Context, Result};
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::{
+    ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use parquet::arrow::ArrowWriter;
+use serde::Serialize;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info};
+
+/// Flush and rotation options for the Parquet sink
+#[derive(Debug, Clone)]
+pub struct ParquetOptions {
+    /// Flush a row group once a partition buffers this many rows
+    pub row_group_rows: usize,
+    /// Flush buffered rows at least this often
+    pub flush_interval: Duration,
+    /// Close a file after this many row groups
+    pub row_groups_per_file: usize,
+}
+
+/// Event sink writing partitioned Parquet files
+pub struct ParquetSink {
+    inner: Arc<Inner>,
+    /// Background task flushing on `flush_interval`
+    flusher: JoinHandle<()>,
+}
+
+/// State shared with the background flusher
+struct Inner {
+    dir: PathBuf,
+    options: ParquetOptions,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    partitions: HashMap<Partition, PartitionWriter>,
+    /// Index of the next file, unique within this process
+    next_file: u64,
+}
+
+/// Table the event belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Table {
+    Log,
+    Account,
+    Commitment,
+}
+
+impl Table {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Table::Log => "log_events",
+            Table::Account => "account_events",
+            Table::Commitment => "commitment_events",
+        }
+    }
+}
+
+/// Partition key: table, event date, and program id for log events
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Partition {
+    table: Table,
+    date: String,
+    program_id: Option<String>,
+}
+
+impl Partition {
+    fn for_event(event: &Event) -> Self {
+        match event {
+            Event::Log(event) => Partition {
+                table: Table::Log,
+                date: event_date(&event.timestamp),
+                program_id: Some(partition_value(&event.program_id)),
+            },
+            Event::Account(event) => Partition {
+                table: Table::Account,
+                date: event_date(&event.timestamp),
+                program_id: None,
+            },
+            Event::Commitment(event) => Partition {
+                table: Table::Commitment,
+                date: event_date(&event.timestamp),
+                program_id: None,
+            },
+        }
+    }
+
+    /// Directory of this partition below the sink root
+    fn dir(&self, root: &Path) -> PathBuf {
+        let mut dir = root
+            .join(self.table.as_str())
+            .join(format!("date={}", self.date));
+        if let Some(program_id) = &self.program_id {
+            dir = dir.join(format!("program_id={}", program_id));
+        }
+        dir
+    }
+}
+
+/// Open file and pending rows for one partition
+struct PartitionWriter {
+    /// Final path; the file is written at `<path>.inprogress` until closed
+    path: PathBuf,
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+    table: Table,
+    /// Events not yet written as a row group
+    pending: Vec<Envelope>,
+    row_groups: usize,
+    last_flush: Instant,
+}
+
+impl PartitionWriter {
+    /// Writer for `file`, which was created at `path`'s in-progress name
+    fn new(path: PathBuf, file: File, table: Table) -> Result<Self> {
+        let schema = match table {
+            Table::Log => log_schema(),
+            Table::Account => account_schema(),
+            Table::Commitment => commitment_schema(),
+        };
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+
+        let writer = ArrowWriter::try_new(file, Arc::clone(&schema), Some(props))
+            .context("Failed to create Parquet writer")?;
+
+        Ok(Self {
+            path,
+            writer,
+            schema,
+            table,
+            pending: Vec::new(),
+            row_groups: 0,
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Write pending rows as one row group
+    fn flush_row_group(&mut self) -> Result<()> {
+        self.last_flush = Instant::now();
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let batch = match build_batch(self.table, &self.schema, &self.pending) {
+            Ok(batch) => batch,
+            Err(e) => {
+                // One bad row must not wedge the partition: drop the rows
+                // that fail on their own and write the rest
+                error!("{:#}; checking rows of {} one by one", e, self.path.display());
+                let (table, schema) = (self.table, &self.schema);
+                self.pending.retain(|row| {
+                    match build_batch(table, schema, std::slice::from_ref(row)) {
+                        Ok(_) => true,
+                        Err(e) => {
+                            error!("Dropping Parquet row at slot {}: {:#}", row.event.slot(), e);
+                            false
+                        }
+                    }
+                });
+                match build_batch(self.table, &self.schema, &self.pending) {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        self.pending.clear();
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        self.writer.write(&batch).context("Failed to write Parquet rows")?;
+        self.writer.flush().context("Failed to flush Parquet row group")?;
+
+        debug!("Wrote {} rows to {}", self.pending.len(), self.path.display());
+        self.pending.clear();
+        self.row_groups += 1;
+        Ok(())
+    }
+
+    /// Flush pending rows, write the footer, and publish the file
+    fn close(mut self) -> Result<PathBuf> {
+        self.flush_row_group()?;
+        self.writer.close().context("Failed to write Parquet footer")?;
+        fs::rename(in_progress_path(&self.path), &self.path)
+            .with_context(|| format!("Failed to publish {}", self.path.display()))?;
+        info!("Closed Parquet file {}", self.path.display());
+        Ok(self.path)
+    }
+}
+
+impl ParquetSink {
+    /// Create the sink rooted at `dir` and start the periodic flusher
+    pub fn open(dir: impl AsRef<Path>, options: ParquetOptions) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create Parquet directory: {}", dir.display()))?;
+
+        let inner = Arc::new(Inner {
+            dir,
+            options,
+            state: Mutex::new(State::default()),
+        });
+        let flusher = tokio::spawn(run_flusher(
+            Arc::downgrade(&inner),
+            inner.options.flush_interval,
+        ));
+
+        Ok(Self { inner, flusher })
+    }
+}
+
+#[async_trait]
+impl EventSink for ParquetSink {
+    fn name(&self) -> &str {
+        "parquet"
+    }
+
+    async fn write_event(&self, envelope: &Envelope) -> Result<()> {
+        self.inner.write(envelope)
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.flusher.abort();
+        self.inner.close_all()
+    }
+}
+
+impl Drop for ParquetSink {
+    fn drop(&mut self) {
+        self.flusher.abort();
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, State>> {
+        self.state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Parquet writer lock poisoned"))
+    }
+
+    fn write(&self, envelope: &Envelope) -> Result<()> {
+        check_row(envelope)?;
+
+        let mut state = self.lock()?;
+        let state = &mut *state;
+        let partition = Partition::for_event(&envelope.event);
+
+        if !state.partitions.contains_key(&partition) {
+            let writer = self.open_writer(state, &partition)?;
+            state.partitions.insert(partition.clone(), writer);
+        }
+
+        let writer = state
+            .partitions
+            .get_mut(&partition)
+            .expect("partition opened above");
+        writer.pending.push(envelope.clone());
+
+        if writer.pending.len() >= self.options.row_group_rows {
+            writer.flush_row_group()?;
+            if writer.row_groups >= self.options.row_groups_per_file {
+                if let Some(writer) = state.partitions.remove(&partition) {
+                    writer.close()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Open a new file in `partition`, skipping names that already exist
+    fn open_writer(&self, state: &mut State, partition: &Partition) -> Result<PartitionWriter> {
+        let dir = partition.dir(&self.dir);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create partition: {}", dir.display()))?;
+
+        let opened_at = OffsetDateTime::now_utc();
+        loop {
+            let path = dir.join(file_name(opened_at, state.next_file));
+            state.next_file += 1;
+            if path.exists() {
+                continue;
+            }
+            let file = match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(in_progress_path(&path))
+            {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to create Parquet file: {}", path.display())
+                    })
+                }
+            };
+            return PartitionWriter::new(path, file, partition.table);
+        }
+    }
+
+    /// Time-based flush
+    ///
+    /// Partitions with pending rows get a new row group; partitions that
+    /// received nothing since the last tick (e.g. yesterday's date) are closed.
+    /// A failing partition is logged and does not hold up the others.
+    fn flush_due(&self) -> Result<()> {
+        let mut state = self.lock()?;
+        let mut idle = Vec::new();
+        let mut first_error = None;
+
+        for (partition, writer) in state.partitions.iter_mut() {
+            if writer.last_flush.elapsed() < self.options.flush_interval {
+                continue;
+            }
+            if writer.pending.is_empty() {
+                idle.push(partition.clone());
+            } else if let Err(e) = writer.flush_row_group() {
+                error!("{:#}", e);
+                first_error.get_or_insert(e);
+            }
+        }
+
+        for partition in idle {
+            if let Some(writer) = state.partitions.remove(&partition) {
+                if let Err(e) = writer.close() {
+                    error!("{:#}", e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Close every open file
+    fn close_all(&self) -> Result<()> {
+        let mut state = self.lock()?;
+        let mut first_error = None;
+        for (_, writer) in state.partitions.drain() {
+            if let Err(e) = writer.close() {
+                error!("{:#}", e);
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+/// Periodically flush until the sink is dropped
+async fn run_flusher(inner: Weak<Inner>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let Some(inner) = inner.upgrade() else {
+            break;
+        };
+        if let Err(e) = inner.flush_due() {
+            error!("Parquet flush failed: {:#}", e);
+        }
+    }
+}
+
+fn log_schema() -> SchemaRef {
+    let mut fields = vec![
+        timestamp_field(),
+        Field::new("signature", DataType::Utf8, false),
+        Field::new("slot", DataType::Int64, false),
+        Field::new("program_id", DataType::Utf8, false),
+        Field::new(
+            "logs",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            false,
+        ),
+        Field::new("err", DataType::Utf8, true),
+        Field::new("err_name", DataType::Utf8, true),
+        Field::new("extra", DataType::Utf8, true),
+        Field::new("decoded", DataType::Utf8, true),
+    ];
+    fields.extend(envelope_fields("commitment"));
+    Arc::new(Schema::new(fields))
+}
+
+fn account_schema() -> SchemaRef {
+    let mut fields = vec![
+        timestamp_field(),
+        Field::new("pubkey", DataType::Utf8, false),
+        Field::new("slot", DataType::Int64, false),
+        Field::new("lamports", DataType::Int64, false),
+        Field::new("data", DataType::Utf8, false),
+        Field::new("extra", DataType::Utf8, true),
+        Field::new("decoded", DataType::Utf8, true),
+    ];
+    fields.extend(envelope_fields("commitment"));
+    Arc::new(Schema::new(fields))
+}
+
+fn commitment_schema() -> SchemaRef {
+    let mut fields = vec![
+        timestamp_field(),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("commitment", DataType::Utf8, false),
+        Field::new("slot", DataType::Int64, false),
+        Field::new("signature", DataType::Utf8, true),
+        Field::new("program_id", DataType::Utf8, true),
+        Field::new("pubkey", DataType::Utf8, true),
+    ];
+    fields.extend(envelope_fields("subscription_commitment"));
+    Arc::new(Schema::new(fields))
+}
+
+/// Envelope columns ending every table, with the subscription's commitment
+/// level in the column named `commitment`
+fn envelope_fields(commitment: &str) -> Vec<Field> {
+    vec![
+        received_at_field(),
+        block_time_field(),
+        Field::new("seq", DataType::Int64, false),
+        Field::new("schema_version", DataType::Int32, false),
+        Field::new("endpoint", DataType::Utf8, false),
+        Field::new(commitment, DataType::Utf8, false),
+        Field::new("subscription", DataType::Int64, false),
+    ]
+}
+
+fn timestamp_field() -> Field {
+    utc_field("timestamp", false)
+}
+
+fn received_at_field() -> Field {
+    utc_field("received_at", false)
+}
+
+fn block_time_field() -> Field {
+    utc_field("block_time", true)
+}
+
+fn utc_field(name: &str, nullable: bool) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        nullable,
+    )
+}
+
+fn log_batch(schema: &SchemaRef, envelopes: &[Envelope]) -> Result<RecordBatch> {
+    let (envelopes, events): (Vec<&Envelope>, Vec<&LogEvent>) = envelopes
+        .iter()
+        .filter_map(|envelope| match &envelope.event {
+            Event::Log(event) => Some((envelope, event)),
+            _ => None,
+        })
+        .unzip();
+
+    let mut logs = ListBuilder::new(StringBuilder::new());
+    for event in &events {
+        for line in &event.logs {
+            logs.values().append_value(line);
+        }
+        logs.append(true);
+    }
+    let errs = events
+        .iter()
+        .map(|e| e.err.as_ref().map(serde_json::to_string).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(timestamp_array(events.iter().map(|e| e.timestamp.as_str()))?),
+        Arc::new(StringArray::from_iter_values(events.iter().map(|e| &e.signature))),
+        Arc::new(Int64Array::from_iter_values(events.iter().map(|e| e.slot as i64))),
+        Arc::new(StringArray::from_iter_values(events.iter().map(|e| &e.program_id))),
+        Arc::new(logs.finish()),
+        Arc::new(StringArray::from(errs)),
+        Arc::new(StringArray::from_iter(
+            events.iter().map(|e| e.err.as_ref().map(|err| err.name.as_str())),
+        )),
+        Arc::new(object_array(events.iter().map(|e| &e.extra))?),
+        Arc::new(object_array(events.iter().map(|e| &e.decoded))?),
+    ];
+    columns.extend(envelope_arrays(&envelopes)?);
+    RecordBatch::try_new(Arc::clone(schema), columns).context("Failed to build log event batch")
+}
+
+fn account_batch(schema: &SchemaRef, envelopes: &[Envelope]) -> Result<RecordBatch> {
+    let (envelopes, events): (Vec<&Envelope>, Vec<&AccountEvent>) = envelopes
+        .iter()
+        .filter_map(|envelope| match &envelope.event {
+            Event::Account(event) => Some((envelope, event)),
+            _ => None,
+        })
+        .unzip();
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(timestamp_array(events.iter().map(|e| e.timestamp.as_str()))?),
+        Arc::new(StringArray::from_iter_values(events.iter().map(|e| &e.pubkey))),
+        Arc::new(Int64Array::from_iter_values(events.iter().map(|e| e.slot as i64))),
+        Arc::new(Int64Array::from_iter_values(events.iter().map(|e| e.lamports as i64))),
+        Arc::new(StringArray::from_iter_values(events.iter().map(|e| &e.data))),
+        Arc::new(object_array(events.iter().map(|e| &e.extra))?),
+        Arc::new(object_array(events.iter().map(|e| &e.decoded))?),
+    ];
+    columns.extend(envelope_arrays(&envelopes)?);
+    RecordBatch::try_new(Arc::clone(schema), columns)
+        .context("Failed to build account event batch")
+}
+
+fn commitment_batch(schema: &SchemaRef, envelopes: &[Envelope]) -> Result<RecordBatch> {
+    let (envelopes, events): (Vec<&Envelope>, Vec<&CommitmentEvent>) = envelopes
+        .iter()
+        .filter_map(|envelope| match &envelope.event {
+            Event::Commitment(event) => Some((envelope, event)),
+            _ => None,
+        })
+        .unzip();
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(timestamp_array(events.iter().map(|e| e.timestamp.as_str()))?),
+        Arc::new(StringArray::from_iter_values(events.iter().map(|e| e.status.as_str()))),
+        Arc::new(StringArray::from_iter_values(events.iter().map(|e| &e.commitment))),
+        Arc::new(Int64Array::from_iter_values(events.iter().map(|e| e.slot as i64))),
+        Arc::new(StringArray::from_iter(events.iter().map(|e| e.signature.as_deref()))),
+        Arc::new(StringArray::from_iter(events.iter().map(|e| e.program_id.as_deref()))),
+        Arc::new(StringArray::from_iter(events.iter().map(|e| e.pubkey.as_deref()))),
+    ];
+    columns.extend(envelope_arrays(&envelopes)?);
+    RecordBatch::try_new(Arc::clone(schema), columns)
+        .context("Failed to build commitment event batch")
+}
+
+fn build_batch(table: Table, schema: &SchemaRef, envelopes: &[Envelope]) -> Result<RecordBatch> {
+    match table {
+        Table::Log => log_batch(schema, envelopes),
+        Table::Account => account_batch(schema, envelopes),
+        Table::Commitment => commitment_batch(schema, envelopes),
+    }
+}
+
+/// Reject envelopes whose timestamps would fail their row group
+fn check_row(envelope: &Envelope) -> Result<()> {
+    let timestamp = match &envelope.event {
+        Event::Log(event) => &event.timestamp,
+        Event::Account(event) => &event.timestamp,
+        Event::Commitment(event) => &event.timestamp,
+    };
+    parse_micros(timestamp)?;
+    parse_micros(&envelope.received_at)?;
+    if let Some(block_time) = &envelope.block_time {
+        parse_micros(block_time)?;
+    }
+    Ok(())
+}
+
+/// Parse RFC3339 timestamps into a UTC microsecond array
+fn timestamp_array<'a>(
+    timestamps: impl Iterator<Item = &'a str>,
+) -> Result<TimestampMicrosecondArray> {
+    let micros = timestamps.map(parse_micros).collect::<Result<Vec<_>>>()?;
+    Ok(TimestampMicrosecondArray::from(micros).with_timezone("UTC"))
+}
+
+/// Maps such as script hook fields as JSON, null where they are empty
+fn object_array<'a, T: Serialize + 'a>(maps: impl Iterator<Item = &'a T>) -> Result<StringArray> {
+    let json = maps.map(object_column).collect::<Result<Vec<_>, _>>()?;
+    Ok(StringArray::from(json))
+}
+
+/// Columns of [`envelope_fields`]
+fn envelope_arrays(envelopes: &[&Envelope]) -> Result<Vec<ArrayRef>> {
+    Ok(vec![
+        Arc::new(received_at_array(envelopes)?),
+        Arc::new(block_time_array(envelopes)?),
+        Arc::new(Int64Array::from_iter_values(envelopes.iter().map(|e| e.seq as i64))),
+        Arc::new(Int32Array::from_iter_values(envelopes.iter().map(|e| e.schema_version as i32))),
+        Arc::new(StringArray::from_iter_values(envelopes.iter().map(|e| &e.endpoint))),
+        Arc::new(StringArray::from_iter_values(envelopes.iter().map(|e| &e.commitment))),
+        Arc::new(Int64Array::from_iter_values(envelopes.iter().map(|e| e.subscription as i64))),
+    ])
+}
+
+fn received_at_array(envelopes: &[&Envelope]) -> Result<TimestampMicrosecondArray> {
+    timestamp_array(envelopes.iter().map(|e| e.received_at.as_str()))
+}
+
+fn block_time_array(envelopes: &[&Envelope]) -> Result<TimestampMicrosecondArray> {
+    let micros = envelopes
+        .iter()
+        .map(|e| e.block_time.as_deref().map(parse_micros).transpose())
+        .collect::<Result<Vec<_>>>()?;
+    Ok(TimestampMicrosecondArray::from(micros).with_timezone("UTC"))
+}
+
+/// Microseconds since the epoch of an RFC3339 timestamp
+fn parse_micros(timestamp: &str) -> Result<i64> {
+    let parsed = OffsetDateTime::parse(timestamp, &Rfc3339)
+        .with_context(|| format!("Invalid event timestamp: {}", timestamp))?;
+    Ok((parsed.unix_timestamp_nanos() / 1_000) as i64)
+}
+
+/// `YYYY-MM-DD` partition value from an RFC3339 timestamp, `unknown` if it
+/// does not parse
+fn event_date(timestamp: &str) -> String {
+    match OffsetDateTime::parse(timestamp, &Rfc3339) {
+        Ok(parsed) => format!(
+            "{:04}-{:02}-{:02}",
+            parsed.year(),
+            u8::from(parsed.month()),
+            parsed.day()
+        ),
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+/// Partition value for a program id: bytes outside the base58 alphabet are
+/// percent-escaped so the value is always a single, plain path component
+fn partition_value(value: &str) -> String {
+    const BASE58: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    if value.is_empty() {
+        return "unknown".to_string();
+    }
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if BASE58.contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+fn file_name(opened_at: OffsetDateTime, index: u64) -> String {
+    format!(
+        "part-{:04}{:02}{:02}T{:02}{:02}{:02}Z-{:04}.parquet",
+        opened_at.year(),
+        u8::from(opened_at.month()),
+        opened_at.day(),
+        opened_at.hour(),
+        opened_at.minute(),
+        opened_at.second(),
+        index
+    )
+}
+
+fn in_progress_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".inprogress");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::TransactionError;
+    use arrow_array::Array;
+    use serde_json::json;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn options(row_group_rows: usize) -> ParquetOptions {
+        ParquetOptions {
+            row_group_rows,
+            flush_interval: Duration::from_secs(3600),
+            row_groups_per_file: 16,
+        }
+    }
+
+    fn log_event(signature: &str, program_id: &str) -> Event {
+        Event::from(LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            signature.to_string(),
+            12345,
+            program_id.to_string(),
+            vec!["Program log: a".to_string(), "Program log: b".to_string()],
+        ))
+    }
+
+    /// All published `.parquet` files below `dir`
+    fn parquet_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(parquet_files(&path));
+            } else if path.extension().is_some_and(|ext| ext == "parquet") {
+                files.push(path);
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_partitions_by_date_and_program() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = ParquetSink::open(dir.path(), options(100)).unwrap();
+
+        sink.write_event(&Envelope::test(log_event("sig1", "progA"))).await.unwrap();
+        sink.write_event(&Envelope::test(log_event("sig2", "progA"))).await.unwrap();
+        sink.write_event(&Envelope::test(log_event("sig3", "progB"))).await.unwrap();
+
+        // Nothing is published before the files are closed
+        assert!(parquet_files(dir.path()).is_empty());
+        sink.close().await.unwrap();
+
+        let files = parquet_files(dir.path());
+        assert_eq!(files.len(), 2);
+        let partition = dir
+            .path()
+            .join("log_events/date=2024-01-15/program_id=progA");
+        assert!(files[0].starts_with(&partition));
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn test_row_groups_flush_on_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = ParquetSink::open(dir.path(), options(2)).unwrap();
+
+        for i in 0..5 {
+            sink.write_event(&Envelope::test(log_event(&format!("sig{}", i), "progA")))
+                .await
+                .unwrap();
+        }
+        sink.close().await.unwrap();
+
+        let files = parquet_files(dir.path());
+        assert_eq!(files.len(), 1);
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap()).unwrap();
+        // Two full row groups plus the remainder flushed on close
+        assert_eq!(builder.metadata().num_row_groups(), 3);
+        assert_eq!(builder.metadata().file_metadata().num_rows(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_envelope_times_are_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = ParquetSink::open(dir.path(), options(100)).unwrap();
+
+        let mut envelope = Envelope::test(log_event("sig1", "progA"));
+        envelope.received_at = "2024-01-15T10:30:46Z".to_string();
+        envelope.block_time = Some("2024-01-15T10:30:45Z".to_string());
+        sink.write_event(&envelope).await.unwrap();
+        sink.write_event(&Envelope::test(log_event("sig2", "progA"))).await.unwrap();
+        sink.close().await.unwrap();
+
+        let files = parquet_files(dir.path());
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<TimestampMicrosecondArray>()
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(column("received_at").value(0), 1_705_314_646_000_000);
+        let block_time = column("block_time");
+        assert_eq!(block_time.value(0), 1_705_314_645_000_000);
+        assert!(block_time.is_null(1));
+    }
+
+    #[tokio::test]
+    async fn test_envelope_metadata_is_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = ParquetSink::open(dir.path(), options(100)).unwrap();
+
+        let mut log = Envelope::test(log_event("sig1", "progA"));
+        log.seq = 42;
+        log.subscription = 7;
+        let promoted = Envelope::test(CommitmentEvent {
+            timestamp: "2024-01-15T10:30:58Z".to_string(),
+            status: crate::event::CommitmentStatus::Promoted,
+            commitment: "confirmed".to_string(),
+            slot: 12345,
+            signature: Some("sig1".to_string()),
+            program_id: Some("progA".to_string()),
+            pubkey: None,
+        });
+        sink.write_event(&log).await.unwrap();
+        sink.write_event(&promoted).await.unwrap();
+        sink.close().await.unwrap();
+
+        let read = |table: &str| {
+            let file = parquet_files(dir.path())
+                .into_iter()
+                .find(|path| path.starts_with(dir.path().join(table)))
+                .unwrap();
+            ParquetRecordBatchReaderBuilder::try_new(File::open(file).unwrap())
+                .unwrap()
+                .build()
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap()
+        };
+        let strings = |batch: &RecordBatch, name: &str| {
+            let column = batch.column_by_name(name).unwrap();
+            column.as_any().downcast_ref::<StringArray>().unwrap().value(0).to_string()
+        };
+        let int64 = |batch: &RecordBatch, name: &str| {
+            let column = batch.column_by_name(name).unwrap();
+            column.as_any().downcast_ref::<Int64Array>().unwrap().value(0)
+        };
+
+        let batch = read("log_events");
+        assert_eq!(int64(&batch, "seq"), 42);
+        assert_eq!(int64(&batch, "subscription"), 7);
+        let schema_version = batch.column_by_name("schema_version").unwrap();
+        let schema_version = schema_version.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(schema_version.value(0), crate::event::SCHEMA_VERSION as i32);
+        assert_eq!(strings(&batch, "endpoint"), "wss://api.mainnet-beta.solana.com");
+        assert_eq!(strings(&batch, "commitment"), "finalized");
+
+        let batch = read("commitment_events");
+        assert_eq!(strings(&batch, "commitment"), "confirmed");
+        assert_eq!(strings(&batch, "subscription_commitment"), "finalized");
+        assert_eq!(int64(&batch, "subscription"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_errors_are_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = ParquetSink::open(dir.path(), options(100)).unwrap();
+
+        let err = TransactionError::decode(json!({"InstructionError": [0, {"Custom": 6001}]}));
+        let mut failed = log_event("sig1", "progA");
+        if let Event::Log(event) = &mut failed {
+            event.err = Some(err.clone());
+        }
+        sink.write_event(&Envelope::test(failed)).await.unwrap();
+        sink.write_event(&Envelope::test(log_event("sig2", "progA"))).await.unwrap();
+        sink.close().await.unwrap();
+
+        let files = parquet_files(dir.path());
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .clone()
+        };
+        let stored: TransactionError = serde_json::from_str(column("err").value(0)).unwrap();
+        assert_eq!(stored, err);
+        assert_eq!(column("err_name").value(0), "InstructionError");
+        assert!(column("err").is_null(1));
+        assert!(column("err_name").is_null(1));
+    }
+
+    #[tokio::test]
+    async fn test_script_fields_are_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = ParquetSink::open(dir.path(), options(100)).unwrap();
+
+        let mut enriched = log_event("sig1", "progA");
+        if let Event::Log(event) = &mut enriched {
+            event.extra.insert("team".to_string(), json!("payments"));
+        }
+        sink.write_event(&Envelope::test(enriched)).await.unwrap();
+        sink.write_event(&Envelope::test(log_event("sig2", "progA"))).await.unwrap();
+        sink.close().await.unwrap();
+
+        let files = parquet_files(dir.path());
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let extra = batch
+            .column_by_name("extra")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(extra.value(0), r#"{"team":"payments"}"#);
+        assert!(extra.is_null(1));
+    }
+
+    #[tokio::test]
+    async fn test_decoded_output_is_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = ParquetSink::open(dir.path(), options(100)).unwrap();
+
+        let mut decoded = log_event("sig1", "progA");
+        if let Event::Log(event) = &mut decoded {
+            event.decoded.insert("progA".to_string(), json!({"kind": "swap"}));
+        }
+        sink.write_event(&Envelope::test(decoded)).await.unwrap();
+        sink.write_event(&Envelope::test(log_event("sig2", "progA"))).await.unwrap();
+        sink.close().await.unwrap();
+
+        let files = parquet_files(dir.path());
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let decoded = batch
+            .column_by_name("decoded")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(decoded.value(0), r#"{"progA":{"kind":"swap"}}"#);
+        assert!(decoded.is_null(1));
+    }
+
+    #[test]
+    fn test_account_partition_has_no_program() {
+        let event = Event::from(AccountEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "pubkey123".to_string(),
+            12345,
+            1000000,
+            "base64data".to_string(),
+        ));
+        let partition = Partition::for_event(&event);
+        assert_eq!(
+            partition.dir(Path::new("/data")),
+            PathBuf::from("/data/account_events/date=2024-01-15")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unparsable_timestamp_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = ParquetSink::open(dir.path(), options(2)).unwrap();
+
+        let mut bad = log_event("bad", "progA");
+        if let Event::Log(event) = &mut bad {
+            event.timestamp = "not a timestamp".to_string();
+        }
+        assert!(sink.write_event(&Envelope::test(bad)).await.is_err());
+        for i in 0..3 {
+            sink.write_event(&Envelope::test(log_event(&format!("sig{}", i), "progA")))
+                .await
+                .unwrap();
+        }
+        sink.close().await.unwrap();
+
+        let files = parquet_files(dir.path());
+        assert_eq!(files.len(), 1);
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap()).unwrap();
+        assert_eq!(builder.metadata().file_metadata().num_rows(), 3);
+    }
+
+    #[test]
+    fn test_bad_row_does_not_block_partition() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.parquet");
+        let file = File::create(in_progress_path(&path)).unwrap();
+        let mut writer = PartitionWriter::new(path, file, Table::Log).unwrap();
+
+        let mut bad = log_event("bad", "progA");
+        if let Event::Log(event) = &mut bad {
+            event.timestamp = "not a timestamp".to_string();
+        }
+        writer.pending.push(Envelope::test(log_event("sig1", "progA")));
+        writer.pending.push(Envelope::test(bad));
+        writer.pending.push(Envelope::test(log_event("sig2", "progA")));
+        writer.flush_row_group().unwrap();
+        assert!(writer.pending.is_empty());
+
+        let path = writer.close().unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(builder.metadata().file_metadata().num_rows(), 2);
+    }
+
+    #[test]
+    fn test_partition_values_are_sanitized() {
+        let mut event = log_event("sig1", "../../etc");
+        if let Event::Log(log) = &mut event {
+            log.timestamp = "../x/2024-01-15".to_string();
+        }
+        let partition = Partition::for_event(&event);
+        assert_eq!(
+            partition.dir(Path::new("/data")),
+            PathBuf::from("/data/log_events/date=unknown/program_id=%2E%2E%2F%2E%2E%2Fetc")
+        );
+
+        assert_eq!(partition_value("a\\b"), "a%5Cb");
+        assert_eq!(partition_value(""), "unknown");
+        assert_eq!(event_date("2024-01-15T23:30:00-05:00"), "2024-01-15");
+        assert_eq!(event_date("2024-1-5"), "unknown");
+    }
+
+    #[tokio::test]
+    async fn test_existing_files_are_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        for signature in ["sig1", "sig2"] {
+            // Each sink restarts its file index at zero
+            let sink = ParquetSink::open(dir.path(), options(100)).unwrap();
+            sink.write_event(&Envelope::test(log_event(signature, "progA")))
+                .await
+                .unwrap();
+            sink.close().await.unwrap();
+        }
+
+        let files = parquet_files(dir.path());
+        assert_eq!(files.len(), 2);
+        for file in files {
+            let builder =
+                ParquetRecordBatchReaderBuilder::try_new(File::open(&file).unwrap()).unwrap();
+            assert_eq!(builder.metadata().file_metadata().num_rows(), 1);
+        }
+    }
+}
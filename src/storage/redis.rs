@@ -0,0 +1,174 @@
+//! Redis Streams event sink
+//!
+//! Each event is appended with `XADD` to a stream per subscription:
+//! `{prefix}:logs:<program_id>` or `{prefix}:account:<pubkey>`. Streams are
+//! trimmed with `MAXLEN ~ n`, so readers always find roughly the most recent
+//! `n` events without the cost of exact trimming. Entries carry the slot and
+//! the event as JSON.
+
+use super::EventSink;
+use crate::event::{redact_endpoint, Envelope, Event};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use tracing::info;
+
+/// Connection options for the Redis sink
+#[derive(Debug, Clone)]
+pub struct RedisOptions {
+    /// Server URL, e.g. `redis://localhost:6379`
+    pub url: String,
+    /// Stream key prefix, e.g. `solana`
+    pub key_prefix: String,
+    /// Approximate number of entries kept per stream
+    pub max_len: usize,
+}
+
+/// Event sink appending to Redis streams
+pub struct RedisSink {
+    /// Multiplexed connection that reconnects on failure
+    conn: ConnectionManager,
+    key_prefix: String,
+    max_len: usize,
+}
+
+impl RedisSink {
+    /// Connect to the server
+    pub async fn connect(options: RedisOptions) -> Result<Self> {
+        // The URL may carry a password, so only its scheme and host are logged
+        let endpoint = redact_endpoint(&options.url);
+        let client = redis::Client::open(options.url.as_str())
+            .with_context(|| format!("Invalid REDIS_URL: {}", endpoint))?;
+        let conn = ConnectionManager::new(client)
+            .await
+            .with_context(|| format!("Failed to connect to Redis at {}", endpoint))?;
+
+        info!(
+            "Connected to Redis at {} (MAXLEN ~ {})",
+            endpoint, options.max_len
+        );
+
+        Ok(Self {
+            conn,
+            key_prefix: options.key_prefix,
+            max_len: options.max_len,
+        })
+    }
+}
+
+#[async_trait]
+impl EventSink for RedisSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    async fn write_event(&self, envelope: &Envelope) -> Result<()> {
+        let payload =
+            serde_json::to_string(envelope).context("Failed to serialize event to JSON")?;
+        let key = stream_key(&self.key_prefix, &envelope.event);
+
+        let mut conn = self.conn.clone();
+        let _id: String = xadd_command(&key, self.max_len, envelope.event.slot(), &payload)
+            .query_async(&mut conn)
+            .await
+            .context("Failed to XADD event to Redis")?;
+
+        Ok(())
+    }
+}
+
+/// Stream key for an event: `{prefix}:logs:<program_id>` or `{prefix}:account:<pubkey>`
+///
+/// Commitment events go to the stream of the event they follow up on.
+fn stream_key(prefix: &str, event: &Event) -> String {
+    format!("{}:{}:{}", prefix, event.subscription_kind(), event.entity_key())
+}
+
+/// `XADD <key> MAXLEN ~ <max_len> * slot <slot> event <json>`
+fn xadd_command(key: &str, max_len: usize, slot: u64, payload: &str) -> redis::Cmd {
+    let mut cmd = redis::cmd("XADD");
+    cmd.arg(key)
+        .arg("MAXLEN")
+        .arg("~")
+        .arg(max_len)
+        .arg("*")
+        .arg("slot")
+        .arg(slot)
+        .arg("event")
+        .arg(payload);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::LogEvent;
+
+    fn log_event() -> Event {
+        Event::from(LogEvent::new(
+            "2024-01-15T10:30:45Z".to_string(),
+            "signature123".to_string(),
+            12345,
+            "program123".to_string(),
+            vec!["Program log: hello".to_string()],
+        ))
+    }
+
+    #[test]
+    fn test_stream_key() {
+        assert_eq!(stream_key("solana", &log_event()), "solana:logs:program123");
+    }
+
+    #[test]
+    fn test_xadd_command_trims_approximately() {
+        let packed =
+            xadd_command("solana:logs:program123", 1000, 12345, "{}").get_packed_command();
+        let packed = String::from_utf8(packed).unwrap();
+        // RESP bulk strings: each `$<len>` line is followed by the argument
+        let lines: Vec<&str> = packed.split("\r\n").collect();
+        let args: Vec<&str> = lines
+            .windows(2)
+            .filter(|pair| pair[0].starts_with('$'))
+            .map(|pair| pair[1])
+            .collect();
+        assert_eq!(
+            args,
+            vec![
+                "XADD",
+                "solana:logs:program123",
+                "MAXLEN",
+                "~",
+                "1000",
+                "*",
+                "slot",
+                "12345",
+                "event",
+                "{}"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server (REDIS_TEST_URL)"]
+    async fn test_xadd_against_local_redis() {
+        let url = std::env::var("REDIS_TEST_URL").expect("REDIS_TEST_URL not set");
+        let prefix = format!("solana-test-{}", std::process::id());
+        let sink = RedisSink::connect(RedisOptions {
+            url,
+            key_prefix: prefix.clone(),
+            max_len: 10,
+        })
+        .await
+        .unwrap();
+
+        for _ in 0..3 {
+            sink.write_event(&Envelope::test(log_event())).await.unwrap();
+        }
+
+        let key = format!("{}:logs:program123", prefix);
+        let mut conn = sink.conn.clone();
+        let len: usize = redis::cmd("XLEN").arg(&key).query_async(&mut conn).await.unwrap();
+        assert_eq!(len, 3);
+        let _: () = redis::cmd("DEL").arg(&key).query_async(&mut conn).await.unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+//! Upload of closed JSONL segments to S3-compatible object storage
+//!
+//! The JSONL writer hands every rotated segment to a [`SegmentUploader`],
+//! which uploads it in the background under a key rendered from
+//! `S3_KEY_TEMPLATE`. Uploads are sent with a SHA-256 checksum the server
+//! verifies, confirmed with a `HEAD` size check, and retried with backoff.
+//! The local file is only deleted after a confirmed upload; a file that is
+//! kept gets an empty `<segment>.uploaded` marker instead, so segments left
+//! without one by an earlier run can be queued again at startup.
+
+use crate::metrics::MetricsRegistry;
+use crate::rpc::calculate_backoff;
+use anyhow::{Context, Result};
+use object_store::aws::{AmazonS3Builder, Checksum};
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// A segment the writer has finished and closed
+#[derive(Debug, Clone)]
+pub struct ClosedSegment {
+    pub path: PathBuf,
+    /// When the segment was opened, used for `{date}` in keys
+    pub opened_at: OffsetDateTime,
+}
+
+/// Bucket and behavior options for segment uploads
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+    pub bucket: String,
+    pub region: String,
+    /// Custom endpoint for S3-compatible stores such as MinIO
+    pub endpoint: Option<String>,
+    /// Object key template, e.g. `{program_id}/{date}/{filename}`
+    pub key_template: String,
+    /// Value substituted for `{program_id}`
+    pub program_id: String,
+    /// Upload attempts per segment
+    pub max_attempts: u32,
+    /// Remove the local file after a confirmed upload
+    pub delete_after_upload: bool,
+}
+
+impl UploadOptions {
+    /// Build an S3 client; credentials come from the standard `AWS_*` variables
+    pub fn build_store(&self) -> Result<Arc<dyn ObjectStore>> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&self.bucket)
+            .with_region(&self.region)
+            .with_checksum_algorithm(Checksum::SHA256);
+        if let Some(endpoint) = &self.endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        let store = builder.build().context("Failed to configure S3 client")?;
+        Ok(Arc::new(store))
+    }
+}
+
+/// Background uploader fed by the JSONL writer
+pub struct SegmentUploader {
+    /// Queue of closed segments; `None` once shut down
+    tx: Mutex<Option<mpsc::UnboundedSender<ClosedSegment>>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SegmentUploader {
+    /// Start the upload task
+    pub fn spawn(
+        store: Arc<dyn ObjectStore>,
+        options: UploadOptions,
+        metrics: MetricsRegistry,
+    ) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<ClosedSegment>();
+
+        let task = tokio::spawn(async move {
+            while let Some(segment) = rx.recv().await {
+                upload_with_retry(store.as_ref(), &options, &metrics, &segment).await;
+            }
+        });
+
+        Self {
+            tx: Mutex::new(Some(tx)),
+            task: Mutex::new(Some(task)),
+        }
+    }
+
+    /// Queue a closed segment for upload
+    pub fn enqueue(&self, segment: ClosedSegment) {
+        let sent = match self.tx.lock() {
+            Ok(tx) => tx.as_ref().is_some_and(|tx| tx.send(segment.clone()).is_ok()),
+            Err(_) => false,
+        };
+        if !sent {
+            warn!(
+                "Segment uploader stopped, not uploading {}",
+                segment.path.display()
+            );
+        }
+    }
+
+    /// Stop accepting segments and wait for queued uploads to finish
+    pub async fn shutdown(&self) {
+        if let Ok(mut tx) = self.tx.lock() {
+            tx.take();
+        }
+        let task = self.task.lock().ok().and_then(|mut task| task.take());
+        if let Some(task) = task {
+            if let Err(e) = task.await {
+                error!("Segment upload task failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Upload a segment, retrying with backoff, then optionally delete it
+async fn upload_with_retry(
+    store: &dyn ObjectStore,
+    options: &UploadOptions,
+    metrics: &MetricsRegistry,
+    segment: &ClosedSegment,
+) {
+    let key = render_key(&options.key_template, &options.program_id, segment);
+
+    for attempt in 0..options.max_attempts.max(1) {
+        if attempt > 0 {
+            tokio::time::sleep(calculate_backoff(attempt - 1, 30)).await;
+        }
+
+        match upload_segment(store, &segment.path, &key).await {
+            Ok(size) => {
+                info!(
+                    "Uploaded {} to {} ({} bytes)",
+                    segment.path.display(),
+                    key,
+                    size
+                );
+                metrics
+                    .segment_uploads_total
+                    .with_label_values(&["success"])
+                    .inc();
+
+                if options.delete_after_upload {
+                    if let Err(e) = tokio::fs::remove_file(&segment.path).await {
+                        error!("Failed to delete {}: {}", segment.path.display(), e);
+                    }
+                } else if let Err(e) = tokio::fs::write(upload_marker(&segment.path), b"").await {
+                    // Only costs a repeated upload after the next restart
+                    warn!("Failed to mark {} as uploaded: {}", segment.path.display(), e);
+                }
+                return;
+            }
+            Err(e) => warn!(
+                "Upload of {} failed (attempt {}/{}): {:#}",
+                segment.path.display(),
+                attempt + 1,
+                options.max_attempts,
+                e
+            ),
+        }
+    }
+
+    error!(
+        "Giving up on uploading {}; the local copy is kept",
+        segment.path.display()
+    );
+    metrics
+        .segment_uploads_total
+        .with_label_values(&["failure"])
+        .inc();
+}
+
+/// Path of the marker recording that `segment` was uploaded
+pub fn upload_marker(segment: &Path) -> PathBuf {
+    let mut marker = segment.as_os_str().to_owned();
+    marker.push(".uploaded");
+    PathBuf::from(marker)
+}
+
+/// Upload one file and confirm the stored object has the same size
+async fn upload_segment(store: &dyn ObjectStore, path: &Path, key: &str) -> Result<u64> {
+    let body = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read segment {}", path.display()))?;
+    let size = body.len() as u64;
+    let location = ObjectPath::parse(key).with_context(|| format!("Invalid object key: {}", key))?;
+
+    store
+        .put(&location, PutPayload::from(body))
+        .await
+        .context("PUT failed")?;
+
+    let meta = store.head(&location).await.context("HEAD failed")?;
+    if meta.size != size {
+        anyhow::bail!(
+            "Size mismatch after upload: local {} bytes, remote {} bytes",
+            size,
+            meta.size
+        );
+    }
+
+    Ok(size)
+}
+
+/// Render an object key from a template
+///
+/// Supported placeholders: `{program_id}`, `{date}` (`YYYY-MM-DD` the segment
+/// was opened), `{segment}` (file name without extensions) and `{filename}`.
+pub fn render_key(template: &str, program_id: &str, segment: &ClosedSegment) -> String {
+    let filename = segment
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = filename
+        .split_once(".jsonl")
+        .map_or(filename.as_str(), |(stem, _)| stem);
+    let opened_at = segment.opened_at;
+    let date = format!(
+        "{:04}-{:02}-{:02}",
+        opened_at.year(),
+        u8::from(opened_at.month()),
+        opened_at.day()
+    );
+
+    template
+        .replace("{program_id}", program_id)
+        .replace("{date}", &date)
+        .replace("{segment}", stem)
+        .replace("{filename}", &filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+
+    fn options(delete_after_upload: bool) -> UploadOptions {
+        UploadOptions {
+            bucket: "events".to_string(),
+            region: "us-east-1".to_string(),
+            endpoint: None,
+            key_template: "{program_id}/{date}/{segment}.jsonl.zst".to_string(),
+            program_id: "program123".to_string(),
+            max_attempts: 3,
+            delete_after_upload,
+        }
+    }
+
+    fn segment(path: PathBuf) -> ClosedSegment {
+        ClosedSegment {
+            path,
+            // 2024-01-15T10:30:45Z
+            opened_at: OffsetDateTime::from_unix_timestamp(1_705_314_645).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_render_key() {
+        let segment = segment(PathBuf::from("./events-20240115T103045Z-0003.jsonl.zst"));
+        assert_eq!(
+            render_key("{program_id}/{date}/{segment}.jsonl.zst", "program123", &segment),
+            "program123/2024-01-15/events-20240115T103045Z-0003.jsonl.zst"
+        );
+        assert_eq!(
+            render_key("raw/{filename}", "program123", &segment),
+            "raw/events-20240115T103045Z-0003.jsonl.zst"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_delete_local_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events-20240115T103045Z-0000.jsonl.zst");
+        std::fs::write(&path, b"compressed bytes").unwrap();
+
+        let store = Arc::new(InMemory::new());
+        let metrics = MetricsRegistry::default();
+        let uploader = SegmentUploader::spawn(store.clone(), options(true), metrics.clone());
+
+        uploader.enqueue(segment(path.clone()));
+        uploader.shutdown().await;
+
+        let key = ObjectPath::from("program123/2024-01-15/events-20240115T103045Z-0000.jsonl.zst");
+        let stored = store.get(&key).await.unwrap().bytes().await.unwrap();
+        assert_eq!(stored.as_ref(), b"compressed bytes");
+        assert!(!path.exists());
+        assert!(!upload_marker(&path).exists());
+        assert_eq!(
+            metrics.segment_uploads_total.with_label_values(&["success"]).get(),
+            1.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kept_segment_is_marked_uploaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events-20240115T103045Z-0000.jsonl.zst");
+        std::fs::write(&path, b"compressed bytes").unwrap();
+
+        let uploader =
+            SegmentUploader::spawn(Arc::new(InMemory::new()), options(false), MetricsRegistry::default());
+        uploader.enqueue(segment(path.clone()));
+        uploader.shutdown().await;
+
+        assert!(path.exists());
+        assert!(upload_marker(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload_is_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.jsonl.zst");
+
+        let metrics = MetricsRegistry::default();
+        let options = UploadOptions {
+            max_attempts: 1,
+            ..options(true)
+        };
+        let uploader = SegmentUploader::spawn(Arc::new(InMemory::new()), options, metrics.clone());
+
+        uploader.enqueue(segment(missing));
+        uploader.shutdown().await;
+
+        assert_eq!(
+            metrics.segment_uploads_total.with_label_values(&["failure"]).get(),
+            1.0
+        );
+    }
+}